@@ -0,0 +1,243 @@
+use crate::registry::{Observe, Sample, Value};
+use crate::sync;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+type Children<C> = RwLock<HashMap<Vec<String>, Arc<C>>>;
+
+/// A family of counters, one for each unique set of label values.
+///
+/// The label names are fixed at construction, the label values are given on each access. The counter of a set of label values
+/// is created on first access, using the given constructor. Usually, the counters are exact or sharded primitive counters.
+///
+/// Looking up a counter takes a read lock and allocates, so on hot paths, keep the handle returned by [with_labels](#method.with_labels)
+/// and count on that instead. Handles stay valid, even if their counter is removed from the family.
+///
+/// A family can be registered in the [registry](../registry/index.html), reporting one labelled sample per counter,
+/// and the sum of all counters as its value.
+///
+/// # Example
+/// ```
+/// use global_counter::family::CounterVec;
+/// use global_counter::primitive::exact::CounterU64;
+///
+/// static REQUESTS: CounterVec<CounterU64> = CounterVec::new(&["method", "status"], || CounterU64::new(0));
+///
+/// fn main(){
+///     let ok = REQUESTS.with_labels(&["GET", "200"]);
+///     ok.inc();
+///     ok.inc();
+///     REQUESTS.with_labels(&["POST", "500"]).inc();
+///
+///     assert_eq!(REQUESTS.with_labels(&["GET", "200"]).get(), 2);
+///     assert_eq!(REQUESTS.children().len(), 2);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CounterVec<C> {
+    label_names: &'static [&'static str],
+    new_counter: fn() -> C,
+    children: Lazy<Children<C>>,
+}
+
+impl<C> CounterVec<C> {
+    /// Creates a new, empty counter family with the given label names and counter constructor. Can be used in static contexts.
+    #[inline]
+    pub const fn new(label_names: &'static [&'static str], new_counter: fn() -> C) -> Self {
+        CounterVec {
+            label_names,
+            new_counter,
+            children: Lazy::new(Default::default),
+        }
+    }
+
+    /// Returns the label names of this family.
+    #[inline]
+    pub fn label_names(&self) -> &'static [&'static str] {
+        self.label_names
+    }
+
+    /// Returns a handle to the counter of the given label values, creating it if it does not exist yet.
+    ///
+    /// # Panics
+    /// Panics if the number of label values does not match the number of label names.
+    pub fn with_labels(&self, label_values: &[&str]) -> Arc<C> {
+        let key = self.key(label_values);
+        if let Some(counter) = self.read().get(&key) {
+            return counter.clone();
+        }
+        sync::write(&self.children)
+            .entry(key)
+            .or_insert_with(|| Arc::new((self.new_counter)()))
+            .clone()
+    }
+
+    /// Returns a handle to the counter of the given label values, if it exists.
+    ///
+    /// # Panics
+    /// Panics if the number of label values does not match the number of label names.
+    #[inline]
+    pub fn get(&self, label_values: &[&str]) -> Option<Arc<C>> {
+        let key = self.key(label_values);
+        self.read().get(&key).cloned()
+    }
+
+    /// Removes the counter of the given label values from this family, returning it.
+    ///
+    /// Outstanding handles to the counter stay valid, but are no longer part of this family.
+    /// The next access with the same label values creates a new counter.
+    ///
+    /// # Panics
+    /// Panics if the number of label values does not match the number of label names.
+    #[inline]
+    pub fn remove(&self, label_values: &[&str]) -> Option<Arc<C>> {
+        let key = self.key(label_values);
+        sync::write(&self.children).remove(&key)
+    }
+
+    /// Returns the label values and handles of all counters of this family, ordered by their label values.
+    pub fn children(&self) -> Vec<(Vec<String>, Arc<C>)> {
+        let mut children: Vec<_> = self
+            .read()
+            .iter()
+            .map(|(key, counter)| (key.clone(), counter.clone()))
+            .collect();
+        children.sort_by(|(a, _), (b, _)| a.cmp(b));
+        children
+    }
+
+    #[inline]
+    fn key(&self, label_values: &[&str]) -> Vec<String> {
+        assert_eq!(
+            label_values.len(),
+            self.label_names.len(),
+            "Expected {} label values, got {}",
+            self.label_names.len(),
+            label_values.len()
+        );
+        label_values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[inline]
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<Vec<String>, Arc<C>>> {
+        sync::read(&self.children)
+    }
+}
+
+impl<C: Observe + Send> Observe for CounterVec<C> {
+    /// Returns the sum of all counters, wrapping around on overflow.
+    fn value(&self) -> Value {
+        self.children()
+            .iter()
+            .fold(Value::Unsigned(0), |sum, (_, counter)| {
+                match (sum, counter.value()) {
+                    (Value::Unsigned(a), Value::Unsigned(b)) => Value::Unsigned(a.wrapping_add(b)),
+                    (Value::Unsigned(a), Value::Signed(b)) => {
+                        Value::Signed((a as i128).wrapping_add(b))
+                    }
+                    (Value::Signed(a), Value::Unsigned(b)) => {
+                        Value::Signed(a.wrapping_add(b as i128))
+                    }
+                    (Value::Signed(a), Value::Signed(b)) => Value::Signed(a.wrapping_add(b)),
+                }
+            })
+    }
+
+    fn samples(&self) -> Vec<Sample> {
+        self.children()
+            .into_iter()
+            .map(|(label_values, counter)| Sample {
+                labels: self
+                    .label_names
+                    .iter()
+                    .map(|name| name.to_string())
+                    .zip(label_values)
+                    .collect(),
+                value: counter.value(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::primitive::exact::CounterI32;
+    use crate::primitive::sharded::ShardedCounterUsize;
+    use crate::registry;
+
+    #[test]
+    fn family_children() {
+        static FAMILY: CounterVec<CounterI32> = CounterVec::new(&["a", "b"], || CounterI32::new(5));
+        assert!(FAMILY.get(&["x", "y"]).is_none());
+        FAMILY.with_labels(&["x", "y"]).dec();
+        FAMILY.with_labels(&["x", "z"]).inc();
+        FAMILY.with_labels(&["w", "y"]).inc();
+        assert_eq!(
+            FAMILY.get(&["x", "y"]).map(|counter| counter.get()),
+            Some(4)
+        );
+
+        let children: Vec<_> = FAMILY
+            .children()
+            .into_iter()
+            .map(|(labels, counter)| (labels, counter.get()))
+            .collect();
+        assert_eq!(
+            children,
+            vec![
+                (vec!["w".to_string(), "y".to_string()], 6),
+                (vec!["x".to_string(), "y".to_string()], 4),
+                (vec!["x".to_string(), "z".to_string()], 6),
+            ]
+        );
+        assert_eq!(FAMILY.value(), Value::Signed(16));
+
+        let removed = FAMILY.remove(&["x", "y"]).unwrap();
+        removed.inc();
+        assert_eq!(FAMILY.children().len(), 2);
+        assert_eq!(FAMILY.with_labels(&["x", "y"]).get(), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn family_label_count_mismatch() {
+        static FAMILY: CounterVec<CounterI32> = CounterVec::new(&["a", "b"], || CounterI32::new(0));
+        FAMILY.with_labels(&["x"]);
+    }
+
+    #[test]
+    fn family_handles_par_threaded() {
+        static FAMILY: CounterVec<ShardedCounterUsize> =
+            CounterVec::new(&["thread"], || ShardedCounterUsize::new(0));
+
+        let threads: Vec<_> = (0..5)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let own = FAMILY.with_labels(&[if i % 2 == 0 { "even" } else { "odd" }]);
+                    let all = FAMILY.with_labels(&["all"]);
+                    for _ in 0..10000 {
+                        own.inc();
+                        all.inc();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        assert_eq!(FAMILY.with_labels(&["all"]).get(), 50000);
+        assert_eq!(FAMILY.with_labels(&["even"]).get(), 30000);
+        assert_eq!(FAMILY.with_labels(&["odd"]).get(), 20000);
+
+        registry::register("test_family_handles", &FAMILY);
+        assert!(crate::prometheus::render().unwrap().contains(
+            "test_family_handles{thread=\"all\"} 50000\n\
+             test_family_handles{thread=\"even\"} 30000\n\
+             test_family_handles{thread=\"odd\"} 20000\n"
+        ));
+    }
+}
@@ -1,10 +1,16 @@
 #![allow(unused_macros)]
 
-#[cfg(parking_lot)]
-use parking_lot::Mutex;
+// The locks backing the counter.
+mod backend;
 
-#[cfg(not(parking_lot))]
-use std::sync::Mutex;
+#[cfg(feature = "parking_lot_rwlock")]
+pub use backend::ReadWrite;
+pub use backend::{Backend, Exclusive};
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use core::time::Duration;
 
 /// This trait promises incrementing behaviour.
 /// Implemented for standard integer types.
@@ -30,20 +36,90 @@ macro_rules! imp {
 
 imp![u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize];
 
+/// What a generic counter does, if its lock is poisoned.
+///
+/// A lock is poisoned, if a thread panicked while holding it, e.g. while holding a borrow returned by [get_mut_borrowed](struct.Counter.html#method.get_mut_borrowed).
+/// The counted value may then be in an inconsistent state. Only the stdlibs Mutex poisons, so this only matters if it backs the counter.
+/// Whether it happened can be checked with [is_poisoned](struct.Counter.html#method.is_poisoned).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PoisonPolicy {
+    /// Every later access panics.
+    #[default]
+    Panic,
+    /// Poisoning is ignored, and every later access uses the counted value as the panicking thread left it.
+    Recover,
+    /// The accessors returning a `Result`, i.e. the `try_` and `_timeout` accessors, return [TryLockError::Poisoned](enum.TryLockError.html#variant.Poisoned) for every later access.
+    /// All other accessors panic, just like with `Panic`, as they cannot report an error.
+    Error,
+}
+
+/// The error returned by the `try_` and `_timeout` accessors of a generic counter, if they cannot acquire the lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TryLockError {
+    /// The lock is held by another thread, or by this one, and was not released in time.
+    WouldBlock,
+    /// The lock is poisoned, and the poison policy is [PoisonPolicy::Error](enum.PoisonPolicy.html#variant.Error).
+    Poisoned,
+}
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TryLockError::WouldBlock => "global counter lock held",
+            TryLockError::Poisoned => "global counter lock poisoned",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryLockError {}
+
 /// A generic, gobal counter.
 ///
 /// This counter holds up rusts guarantees of freedom of data-races. Any caveats are clearly pointed out in the documentation.
 ///
 /// This counter is implemented using a Mutex, which can be slow if a lot of contention is involved.
+/// By default, the Mutex is a `parking_lot::Mutex`. Features select other mutexes: `spin_lock` a spin lock,
+/// and disabling the default `parking_lot` feature the stdlibs Mutex. See the [crate documentation](../index.html) for details.
+/// With the `parking_lot_rwlock` feature, an [RwCounter](type.RwCounter.html) is backed by a `parking_lot::RwLock` instead, which lets readers share the lock.
+///
+/// What happens if a thread panics while holding the lock is determined by the counters [PoisonPolicy](enum.PoisonPolicy.html).
 /// To circumvent this, consider extracting the 'counted parts' of your struct into primitives,
 /// which can then be counted by much faster primitive counters. Abstracting can then restore the original interface.
 ///
 /// Avoid premature optimzation though!
 #[derive(Debug, Default)]
-pub struct Counter<T: Inc>(Mutex<T>);
+pub struct Counter<T: Inc, L: Backend = Exclusive>(L::Lock<T>, PoisonPolicy);
+
+/// A generic counter backed by a `parking_lot::RwLock`, letting concurrent `get_borrowed` and `get_cloned` calls share the lock.
+///
+/// As readers share the counted value, the counter can only be shared between threads if the counted value is `Sync`, other than a [Counter](struct.Counter.html).
+///
+/// Requires the `parking_lot_rwlock` feature.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "parking_lot_rwlock")]
+/// # {
+/// use global_counter::generic::RwCounter;
+///
+/// static COUNTER: RwCounter<u32> = RwCounter::new_rw(0);
+/// COUNTER.inc();
+/// // Both borrows share the lock.
+/// let (first, second) = (COUNTER.get_borrowed(), COUNTER.get_borrowed());
+/// assert_eq!(*first + *second, 2);
+/// # }
+/// ```
+#[cfg(feature = "parking_lot_rwlock")]
+pub type RwCounter<T> = Counter<T, ReadWrite>;
 
 /// Creates a new global, generic counter, starting from the given value.
 ///
+/// Requires the `std` feature. Without it, use a `static` holding a counter created by [Counter::new](generic/struct.Counter.html#method.new) instead.
+///
+/// If a name is given as well, the counter is registered under that name in the [registry](registry/index.html) on first access,
+/// optionally followed by a help text for exporters. This requires the counted type to be a primitive integer type.
+///
 /// # Example
 /// ```
 /// # #[macro_use] use crate::global_counter::*;
@@ -56,12 +132,34 @@ pub struct Counter<T: Inc>(Mutex<T>);
 ///     assert_eq!(COUNTER_NAME.get_cloned(), 1);
 /// }
 /// ```
+///
+/// # Example - Registered
+/// ```
+/// # #[macro_use] use crate::global_counter::*;
+/// use global_counter::registry::{self, Value};
+/// fn main(){
+///     global_counter!(COUNTER_NAME, u32, 0, "counter_name");
+///     COUNTER_NAME.inc();
+///     assert_eq!(registry::get("counter_name"), Some(Value::Unsigned(1)));
+/// }
+/// ```
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! global_counter {
     ($name:ident, $type:ident, $value:expr) => {
         static $name: ::global_counter::global_counter_macro_dependencies::Lazy<::global_counter::generic::Counter<$type>> =
         ::global_counter::global_counter_macro_dependencies::Lazy::new(|| ::global_counter::generic::Counter::new($value));
     };
+    ($name:ident, $type:ident, $value:expr, $registered_name:expr) => {
+        global_counter!($name, $type, $value, $registered_name, "");
+    };
+    ($name:ident, $type:ident, $value:expr, $registered_name:expr, $help:expr) => {
+        static $name: ::global_counter::global_counter_macro_dependencies::Lazy<::global_counter::generic::Counter<$type>> =
+        ::global_counter::global_counter_macro_dependencies::Lazy::new(|| {
+            ::global_counter::registry::register_with($registered_name, $help, &[], &$name);
+            ::global_counter::generic::Counter::new($value)
+        });
+    };
 }
 
 // A hack for local usage.
@@ -71,12 +169,25 @@ macro_rules! global_counter_2 {
         static $name: Lazy<Counter<$type>> =
             Lazy::new(|| Counter::new($value));
     };
+    ($name:ident, $type:ident, $value:expr, $registered_name:expr) => {
+        global_counter_2!($name, $type, $value, $registered_name, "");
+    };
+    ($name:ident, $type:ident, $value:expr, $registered_name:expr, $help:expr) => {
+        use once_cell::sync::Lazy;
+        static $name: Lazy<Counter<$type>> =
+            Lazy::new(|| {
+                crate::registry::register_with($registered_name, $help, &[], &$name);
+                Counter::new($value)
+            });
+    };
 }
 
 /// Creates a new generic, global counter, starting from its default value.
 ///
 /// This macro will fail compilation if the given type is not `Default`.
 ///
+/// Just like with [global_counter!](macro.global_counter.html), a name can be given to register the counter under.
+///
 /// # Example
 /// ```
 /// # #[macro_use] use crate::global_counter::*;
@@ -88,11 +199,18 @@ macro_rules! global_counter_2 {
 ///     assert_eq!(COUNTER_NAME.get_cloned(), 1);
 /// }
 /// ```
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! global_default_counter {
     ($name:ident, $type:ident) => {
         global_counter!($name, $type, $type::default());
     };
+    ($name:ident, $type:ident, $registered_name:expr) => {
+        global_counter!($name, $type, $type::default(), $registered_name);
+    };
+    ($name:ident, $type:ident, $registered_name:expr, $help:expr) => {
+        global_counter!($name, $type, $type::default(), $registered_name, $help);
+    };
 }
 
 // A hack for local usage.
@@ -100,16 +218,47 @@ macro_rules! global_default_counter_2{
     ($name:ident, $type:ident) => {
         global_counter_2!($name, $type, $type::default());
     };
+    ($name:ident, $type:ident, $registered_name:expr) => {
+        global_counter_2!($name, $type, $type::default(), $registered_name);
+    };
 }
 
 impl<T: Inc> Counter<T> {
-    /// Creates a new generic counter.
+    /// Creates a new generic counter. Can be used in static contexts.
     ///
-    /// This function is not const yet. As soon as [Mutex::new()](https://docs.rs/lock_api/*/lock_api/struct.Mutex.html#method.new) is stable as `const fn`, this will be as well, if the `parking_lot` feature is not disabled.
-    /// Then, the exported macros will no longer be needed.
+    /// The exported macros are still useful for start values which cannot be computed in const contexts, like `Default::default()`.
     #[inline]
-    pub fn new(val: T) -> Counter<T> {
-        Counter(Mutex::new(val))
+    pub const fn new(val: T) -> Counter<T> {
+        Self::with_poison_policy(val, PoisonPolicy::Panic)
+    }
+
+    /// Creates a new generic counter with the given poison policy. Can be used in static contexts.
+    #[inline]
+    pub const fn with_poison_policy(val: T, policy: PoisonPolicy) -> Counter<T> {
+        Counter(backend::new(val), policy)
+    }
+}
+
+#[cfg(feature = "parking_lot_rwlock")]
+impl<T: Inc> RwCounter<T> {
+    /// Creates a new generic counter backed by a read-write lock. Can be used in static contexts.
+    #[inline]
+    pub const fn new_rw(val: T) -> RwCounter<T> {
+        Self::rw_with_poison_policy(val, PoisonPolicy::Panic)
+    }
+
+    /// Creates a new generic counter backed by a read-write lock, with the given poison policy. Can be used in static contexts.
+    #[inline]
+    pub const fn rw_with_poison_policy(val: T, policy: PoisonPolicy) -> RwCounter<T> {
+        Counter(backend::new_rw(val), policy)
+    }
+}
+
+impl<T: Inc, L: Backend> Counter<T, L> {
+    /// Returns the poison policy of this counter.
+    #[inline]
+    pub fn poison_policy(&self) -> PoisonPolicy {
+        self.1
     }
 
     /// Returns (basically) an immutable borrow of the underlying value.
@@ -123,9 +272,9 @@ impl<T: Inc> Counter<T> {
     ///
     /// # Good Example - Borrow goes out of scope
     /// ```
-    /// # #[macro_use] use crate::global_counter::*;
+    /// # use global_counter::generic::Counter;
     /// fn main(){
-    ///     global_default_counter!(COUNTER, u8);
+    ///     static COUNTER: Counter<u8> = Counter::new(0);
     ///     assert_eq!(0, *COUNTER.get_borrowed());
     ///
     ///     // The borrow is already out of scope, we can call inc safely.
@@ -136,9 +285,9 @@ impl<T: Inc> Counter<T> {
     ///
     /// # Good Example - At most one concurrent access per thread
     /// ```
-    /// # #[macro_use] use crate::global_counter::*;
+    /// # use global_counter::generic::Counter;
     /// fn main(){
-    ///     global_default_counter!(COUNTER, u8);
+    ///     static COUNTER: Counter<u8> = Counter::new(0);
     ///     assert_eq!(0, *COUNTER.get_borrowed());
     ///     
     ///     // Using this code, there is no danger of data races, race coditions whatsoever.
@@ -169,12 +318,12 @@ impl<T: Inc> Counter<T> {
     ///
     /// # Bad Example
     /// ```no_run
-    /// # #[macro_use] use crate::global_counter::*;
+    /// # use global_counter::generic::Counter;
     /// // We spawn a new thread. This thread will try lockig the counter twice, causing a deadlock.
     /// std::thread::spawn(move || {
     ///
     ///     // We could also use get_cloned with this counter, circumventing all these troubles.
-    ///     global_default_counter!(COUNTER, u32);
+    ///     static COUNTER: Counter<u32> = Counter::new(0);
     ///     
     ///     // The borrow is now alive, and this thread now holds a lock onto the counter.
     ///     let counter_value_borrowed = COUNTER.get_borrowed();
@@ -189,8 +338,8 @@ impl<T: Inc> Counter<T> {
     /// });
     /// ```
     #[inline]
-    pub fn get_borrowed(&self) -> impl std::ops::Deref<Target = T> + '_ {
-        self.lock()
+    pub fn get_borrowed(&self) -> impl Deref<Target = T> + '_ {
+        self.read()
     }
 
     /// Returns a mutable borrow of the counted value, meaning the actual value counted by this counter can be mutated through this borrow.
@@ -198,8 +347,9 @@ impl<T: Inc> Counter<T> {
     /// The constraints pointed out for [get_borrowed](struct.Counter.html#method.get_borrowed) also apply here.
     ///
     /// Although this API is in theory as safe as its immutable equivalent, usage of it is discouraged, as it is highly unidiomatic.
+    /// Prefer [update](#method.update), which cannot hold the lock for longer than the given closure runs.
     #[inline]
-    pub fn get_mut_borrowed(&self) -> impl std::ops::DerefMut<Target = T> + '_ {
+    pub fn get_mut_borrowed(&self) -> impl DerefMut<Target = T> + '_ {
         self.lock()
     }
 
@@ -209,50 +359,260 @@ impl<T: Inc> Counter<T> {
         *self.lock() = val;
     }
 
+    /// Sets the counted value to the given value, returning the previous value.
+    #[inline]
+    pub fn replace(&self, val: T) -> T {
+        core::mem::replace(&mut *self.lock(), val)
+    }
+
+    /// Calls the given closure with a mutable borrow of the counted value, returning whatever the closure returns.
+    ///
+    /// The lock is held exactly while the closure runs, so no other access to the counter can interleave with the update.
+    /// Just like with [get_borrowed](struct.Counter.html#method.get_borrowed), accessing the counter from within the closure deadlocks or panics.
+    ///
+    /// # Example
+    /// ```
+    /// # use global_counter::generic::Counter;
+    /// static COUNTER: Counter<u32> = Counter::new(7);
+    /// let was_odd = COUNTER.update(|val| {
+    ///     let odd = *val % 2 == 1;
+    ///     *val *= 2;
+    ///     odd
+    /// });
+    /// assert!(was_odd);
+    /// assert_eq!(COUNTER.get_cloned(), 14);
+    /// ```
+    #[inline]
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.lock())
+    }
+
     /// Increments the counter, delegating the specific implementation to the [Inc](trait.Inc.html) trait.
     #[inline]
     pub fn inc(&self) {
         self.lock().inc();
     }
 
-    #[cfg(parking_lot)]
+    /// Increments the counter, just like [inc](#method.inc), but never blocks.
+    ///
+    /// Returns [TryLockError::WouldBlock](enum.TryLockError.html#variant.WouldBlock) without counting if the lock is held right now, by another thread or by this one,
+    /// and [TryLockError::Poisoned](enum.TryLockError.html#variant.Poisoned) if it is poisoned and the policy is [PoisonPolicy::Error](enum.PoisonPolicy.html#variant.Error).
+    /// Latency-sensitive code can then skip or defer counting, instead of waiting for the lock.
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    #[inline]
+    pub fn try_inc(&self) -> Result<(), TryLockError> {
+        self.try_lock().map(|mut locked| locked.inc())
+    }
+
+    /// Returns an immutable borrow of the underlying value, just like [get_borrowed](#method.get_borrowed), but never blocks.
+    ///
+    /// Fails in the same cases as [try_inc](#method.try_inc). For an [RwCounter](type.RwCounter.html), a borrow is still returned if other threads only read.
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    #[inline]
+    pub fn try_get_borrowed(&self) -> Result<impl Deref<Target = T> + '_, TryLockError> {
+        self.try_read()
+    }
+
+    /// Returns a mutable borrow of the underlying value, just like [get_mut_borrowed](#method.get_mut_borrowed), but never blocks.
+    ///
+    /// Fails in the same cases as [try_inc](#method.try_inc).
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    #[inline]
+    pub fn try_get_mut_borrowed(&self) -> Result<impl DerefMut<Target = T> + '_, TryLockError> {
+        self.try_lock()
+    }
+
+    /// Increments the counter, just like [inc](#method.inc), but waits at most for the given timeout to acquire the lock.
+    ///
+    /// Returns [TryLockError::WouldBlock](enum.TryLockError.html#variant.WouldBlock) without counting if the timeout elapsed, and fails like [try_inc](#method.try_inc) if the lock is poisoned.
+    /// The parking_lot backends park the waiting thread, the others retry until the timeout elapses.
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn inc_timeout(&self, timeout: Duration) -> Result<(), TryLockError> {
+        self.lock_for(timeout).map(|mut locked| locked.inc())
+    }
+
+    /// Returns an immutable borrow of the underlying value, just like [get_borrowed](#method.get_borrowed), but waits at most for the given timeout.
+    ///
+    /// Fails in the same cases as [inc_timeout](#method.inc_timeout).
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn get_borrowed_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<impl Deref<Target = T> + '_, TryLockError> {
+        self.apply_policy(L::read_for(&self.0, timeout).ok_or(TryLockError::WouldBlock)?)
+    }
+
+    /// Returns a mutable borrow of the underlying value, just like [get_mut_borrowed](#method.get_mut_borrowed), but waits at most for the given timeout.
+    ///
+    /// Fails in the same cases as [inc_timeout](#method.inc_timeout).
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    ///
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn get_mut_borrowed_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<impl DerefMut<Target = T> + '_, TryLockError> {
+        self.lock_for(timeout)
+    }
+
+    /// Returns whether the lock of this counter is poisoned, i.e. a thread panicked while holding it. This is independent of the poison policy.
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        L::is_poisoned(&self.0)
+    }
+
+    #[inline]
+    fn lock(&self) -> L::WriteGuard<'_, T> {
+        self.apply_policy(L::write(&self.0))
+            .unwrap_or_else(|_| poisoned())
+    }
+
+    #[inline]
+    fn read(&self) -> L::ReadGuard<'_, T> {
+        self.apply_policy(L::read(&self.0))
+            .unwrap_or_else(|_| poisoned())
+    }
+
+    #[inline]
+    fn try_lock(&self) -> Result<L::WriteGuard<'_, T>, TryLockError> {
+        self.apply_policy(L::try_write(&self.0).ok_or(TryLockError::WouldBlock)?)
+    }
+
     #[inline]
-    fn lock(&self) -> impl std::ops::DerefMut<Target = T> + '_ {
-        self.0.lock()
+    fn try_read(&self) -> Result<L::ReadGuard<'_, T>, TryLockError> {
+        self.apply_policy(L::try_read(&self.0).ok_or(TryLockError::WouldBlock)?)
     }
 
-    #[cfg(not(parking_lot))]
+    #[cfg(feature = "std")]
     #[inline]
-    fn lock(&self) -> impl std::ops::DerefMut<Target = T> + '_ {
-        self.0.lock().expect("Global counter lock failed. This indicates another user paniced while holding a lock to the counter.")
+    fn lock_for(&self, timeout: Duration) -> Result<L::WriteGuard<'_, T>, TryLockError> {
+        self.apply_policy(L::write_for(&self.0, timeout).ok_or(TryLockError::WouldBlock)?)
     }
+
+    // Returns the guard, unless the lock is poisoned and the policy is not to recover.
+    #[inline]
+    fn apply_policy<G>(&self, guarded: backend::Guarded<G>) -> Result<G, TryLockError> {
+        match (guarded, self.1) {
+            (Ok(guard), _) | (Err(guard), PoisonPolicy::Recover) => Ok(guard),
+            (Err(_), PoisonPolicy::Error) => Err(TryLockError::Poisoned),
+            (Err(_), PoisonPolicy::Panic) => poisoned(),
+        }
+    }
+}
+
+#[cold]
+fn poisoned() -> ! {
+    panic!("Global counter lock failed. This indicates another user paniced while holding a lock to the counter.")
 }
 
-impl<T: Inc + Clone> Counter<T> {
+impl<T: Inc + Clone, L: Backend> Counter<T, L> {
     /// This avoid the troubles of [get_borrowed](struct.Counter.html#method.get_borrowed) by cloning the current value.
     ///
     /// Creating a deadlock using this API should be impossible, it might however violate implicit synchronization assumptions.
     #[inline]
     pub fn get_cloned(&self) -> T {
-        self.lock().clone()
+        self.read().clone()
+    }
+
+    /// Clones the current value, just like [get_cloned](#method.get_cloned), but never blocks.
+    ///
+    /// Fails in the same cases as [try_inc](#method.try_inc).
+    ///
+    /// # Panics
+    ///
+    /// If the lock is poisoned and the poison policy is [PoisonPolicy::Panic](enum.PoisonPolicy.html#variant.Panic), the default.
+    #[inline]
+    pub fn try_get_cloned(&self) -> Result<T, TryLockError> {
+        self.try_read().map(|locked| locked.clone())
     }
 
     /// Increments the counter, returning the previous value, cloned.
     #[inline]
     pub fn inc_cloning(&self) -> T {
-        let mut locked = self.lock();
-        let prev = locked.clone();
-        locked.inc();
-        prev
+        self.update(|val| {
+            let prev = val.clone();
+            val.inc();
+            prev
+        })
+    }
+
+    /// Updates the counted value with the given closure, just like [update](#method.update), returning both the previous and the new value, cloned.
+    ///
+    /// The first element of the returned tuple is the previous value, the second one is the new value.
+    /// Both are cloned under the same lock as the update, so they are exactly what the closure saw and left behind.
+    #[inline]
+    pub fn update_and_get_both<F: FnOnce(&mut T)>(&self, f: F) -> (T, T) {
+        self.update(|val| {
+            let prev = val.clone();
+            f(val);
+            (prev, val.clone())
+        })
     }
 }
 
-impl<T: Inc + Default> Counter<T> {
+impl<T: Inc + Default, L: Backend> Counter<T, L> {
     /// Resets the counter to its default value.
     #[inline]
     pub fn reset(&self) {
         self.set(T::default());
     }
+
+    /// Resets the counter to its default value, returning the previous value.
+    ///
+    /// Both happen under one lock, so other than calling `get_cloned` and `reset`, no concurrent increment is lost in between.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        core::mem::take(&mut *self.lock())
+    }
+}
+
+/// Serializes the current value of the counter.
+///
+/// Just like [get_borrowed](struct.Counter.html#method.get_borrowed), this deadlocks or panics if the serializing thread holds a borrow of the counter.
+#[cfg(feature = "serde")]
+impl<T: Inc + serde::Serialize, L: Backend> serde::Serialize for Counter<T, L> {
+    #[inline]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read().serialize(serializer)
+    }
+}
+
+/// Deserializes a value into a new counter, starting from that value.
+#[cfg(feature = "serde")]
+impl<'de, T: Inc + serde::Deserialize<'de>, L: Backend> serde::Deserialize<'de> for Counter<T, L> {
+    #[inline]
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(|val| Counter(L::new(val), PoisonPolicy::Panic))
+    }
 }
 
 #[cfg(test)]
@@ -560,4 +920,232 @@ mod tests {
         COUNTER.inc();
         assert_eq!(COUNTER.get_cloned(), 1);
     }
+
+    #[test]
+    fn take() {
+        global_counter_2!(COUNTER, i64, 5);
+        COUNTER.inc();
+        assert_eq!(COUNTER.take(), 6);
+        assert_eq!(COUNTER.get_cloned(), 0);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+        let mut taken = 0;
+        for _ in 0..100 {
+            taken += COUNTER.take();
+        }
+        t_0.join().expect("Err joining thread");
+        taken += COUNTER.take();
+        assert_eq!(taken, 10000);
+    }
+
+    #[test]
+    fn update() {
+        static COUNTER: Counter<PanicOnClone> = Counter::new(PanicOnClone(3));
+        assert_eq!(COUNTER.update(|val| std::mem::take(&mut val.0)), 3);
+        assert_eq!(COUNTER.replace(PanicOnClone(5)), PanicOnClone(0));
+        COUNTER.update(|val| val.0 *= 2);
+        assert_eq!(*COUNTER.get_borrowed(), PanicOnClone(10));
+    }
+
+    #[test]
+    fn update_and_get_both_par_threaded() {
+        static COUNTER: Counter<u64> = Counter::new(1);
+        assert_eq!(COUNTER.update_and_get_both(|val| *val *= 3), (1, 3));
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..1000)
+                        .map(|_| COUNTER.update_and_get_both(|val| *val += 2))
+                        .all(|(prev, new)| new == prev + 2)
+                })
+            })
+            .collect();
+        for t in threads {
+            assert!(t.join().expect("Err joining thread"));
+        }
+
+        assert_eq!(COUNTER.get_cloned(), 10003);
+    }
+
+    #[test]
+    fn new_const() {
+        static COUNTER: Counter<u16> = Counter::new(3);
+        COUNTER.inc();
+        assert_eq!(COUNTER.get_cloned(), 4);
+
+        static COUNTER_2: Counter<PanicOnClone> = Counter::new(PanicOnClone(-1));
+        COUNTER_2.inc();
+        assert_eq!(*COUNTER_2.get_borrowed(), PanicOnClone(0));
+    }
+
+    #[test]
+    fn poison_policy() {
+        use crate::generic::{backend, PoisonPolicy, TryLockError};
+        static PANIC: Counter<u32> = Counter::new(0);
+        static RECOVER: Counter<u32> = Counter::with_poison_policy(0, PoisonPolicy::Recover);
+        static ERROR: Counter<u32> = Counter::with_poison_policy(0, PoisonPolicy::Error);
+        assert_eq!(PANIC.poison_policy(), PoisonPolicy::Panic);
+
+        for counter in [&PANIC, &RECOVER, &ERROR] {
+            let t_0 = std::thread::spawn(move || {
+                *counter.get_mut_borrowed() += 1;
+                let _locked = counter.get_mut_borrowed();
+                panic!("Panicking while holding the lock");
+            });
+            assert!(t_0.join().is_err());
+        }
+
+        assert_eq!(PANIC.is_poisoned(), backend::POISONS);
+        let panics = |f: fn()| std::panic::catch_unwind(f).is_err();
+        if backend::POISONS {
+            assert!(panics(|| PANIC.inc()));
+            assert!(panics(|| {
+                let _ = PANIC.try_inc();
+            }));
+            assert!(panics(|| {
+                let _ = PANIC.try_get_cloned();
+            }));
+            assert!(panics(|| {
+                let _ = PANIC.try_get_borrowed();
+            }));
+            assert!(panics(|| {
+                let _ = PANIC.try_get_mut_borrowed();
+            }));
+            #[cfg(feature = "std")]
+            {
+                const TIMEOUT: std::time::Duration = std::time::Duration::from_millis(10);
+                assert!(panics(|| {
+                    let _ = PANIC.inc_timeout(TIMEOUT);
+                }));
+                assert!(panics(|| {
+                    let _ = PANIC.get_borrowed_timeout(TIMEOUT);
+                }));
+                assert!(panics(|| {
+                    let _ = PANIC.get_mut_borrowed_timeout(TIMEOUT);
+                }));
+                assert_eq!(ERROR.inc_timeout(TIMEOUT), Err(TryLockError::Poisoned));
+            }
+            assert_eq!(ERROR.try_get_cloned(), Err(TryLockError::Poisoned));
+            assert_eq!(ERROR.try_inc(), Err(TryLockError::Poisoned));
+            assert!(panics(|| ERROR.inc()));
+        } else {
+            assert_eq!(PANIC.try_get_cloned(), Ok(1));
+            assert_eq!(PANIC.try_inc(), Ok(()));
+            assert_eq!(ERROR.try_inc(), Ok(()));
+        }
+
+        RECOVER.inc();
+        assert_eq!(RECOVER.try_inc(), Ok(()));
+        assert_eq!(RECOVER.try_get_cloned(), Ok(3));
+        assert_eq!(RECOVER.get_cloned(), 3);
+    }
+
+    #[test]
+    fn try_accessors() {
+        use crate::generic::TryLockError;
+        static COUNTER: Counter<u32> = Counter::new(0);
+        assert_eq!(COUNTER.try_inc(), Ok(()));
+
+        let locked = COUNTER.get_mut_borrowed();
+        let t_0 = std::thread::spawn(|| {
+            assert_eq!(COUNTER.try_inc(), Err(TryLockError::WouldBlock));
+            assert!(matches!(
+                COUNTER.try_get_borrowed(),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert!(matches!(
+                COUNTER.try_get_mut_borrowed(),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert_eq!(COUNTER.try_get_cloned(), Err(TryLockError::WouldBlock));
+        });
+        t_0.join().expect("Err joining thread");
+        drop(locked);
+
+        *COUNTER.try_get_mut_borrowed().unwrap() += 1;
+        assert_eq!(*COUNTER.try_get_borrowed().unwrap(), 2);
+        assert_eq!(COUNTER.get_cloned(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timeout_accessors() {
+        use crate::generic::TryLockError;
+        use std::time::Duration;
+        static COUNTER: Counter<u32> = Counter::new(0);
+
+        let locked = COUNTER.get_mut_borrowed();
+        let t_0 = std::thread::spawn(|| {
+            let timeout = Duration::from_millis(10);
+            assert_eq!(COUNTER.inc_timeout(timeout), Err(TryLockError::WouldBlock));
+            assert!(matches!(
+                COUNTER.get_borrowed_timeout(timeout),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert!(matches!(
+                COUNTER.get_mut_borrowed_timeout(timeout),
+                Err(TryLockError::WouldBlock)
+            ));
+        });
+        t_0.join().expect("Err joining thread");
+        // Waiting succeeds once the lock is released in time.
+        let t_1 = std::thread::spawn(|| COUNTER.inc_timeout(Duration::from_secs(10)));
+        std::thread::sleep(Duration::from_millis(20));
+        drop(locked);
+        assert_eq!(t_1.join().expect("Err joining thread"), Ok(()));
+
+        let timeout = Duration::from_secs(1);
+        assert_eq!(COUNTER.inc_timeout(timeout), Ok(()));
+        *COUNTER.get_mut_borrowed_timeout(timeout).unwrap() += 1;
+        assert_eq!(*COUNTER.get_borrowed_timeout(timeout).unwrap(), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn registered() {
+        use crate::registry::{get, Value};
+        {
+            global_counter_2!(COUNTER, i16, -1, "test_generic_registered");
+            COUNTER.inc();
+            COUNTER.inc();
+        }
+        {
+            global_default_counter_2!(COUNTER, u64, "test_generic_registered_default");
+            COUNTER.inc();
+        }
+        assert_eq!(get("test_generic_registered"), Some(Value::Signed(1)));
+        assert_eq!(get("test_generic_registered_default"), Some(Value::Unsigned(1)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        global_counter_2!(COUNTER, u32, 41);
+        COUNTER.inc();
+        let json = serde_json::to_string(&*COUNTER).unwrap();
+        assert_eq!(json, "42");
+
+        let restored: Counter<u32> = serde_json::from_str(&json).unwrap();
+        restored.inc();
+        assert_eq!(restored.get_cloned(), 43);
+    }
+
+    #[cfg(all(feature = "serde", feature = "parking_lot_rwlock"))]
+    #[test]
+    fn serde_rw() {
+        use crate::generic::RwCounter;
+        static COUNTER: RwCounter<u32> = RwCounter::new_rw(41);
+        COUNTER.inc();
+        let json = serde_json::to_string(&COUNTER).unwrap();
+        assert_eq!(json, "42");
+
+        let restored: RwCounter<u32> = serde_json::from_str(&json).unwrap();
+        restored.inc();
+        assert_eq!(restored.get_cloned(), 43);
+    }
 }
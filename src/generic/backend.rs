@@ -0,0 +1,431 @@
+// The locks backing the generic counter.
+// The exclusive lock is selected at compile time by features, as listed in the crate documentation. The read-write lock is a separate backend,
+// so enabling its feature only adds a counter type, and never changes what other crates' counters require of their values.
+// Every backend provides the same guards, non-blocking accessors and, with std, accessors waiting with a timeout,
+// so the counter itself does not care which one is used.
+
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+/// A guard of the lock, wrapped in `Err` if the lock is poisoned, i.e. a previous holder panicked. The guard is usable either way.
+pub type Guarded<G> = Result<G, G>;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A lock backing a [generic counter](struct.Counter.html), either [Exclusive](struct.Exclusive.html) or [ReadWrite](struct.ReadWrite.html).
+///
+/// This trait is sealed. Its items are implementation details.
+pub trait Backend: sealed::Sealed {
+    #[doc(hidden)]
+    type Lock<T>;
+    #[doc(hidden)]
+    type ReadGuard<'a, T: 'a>: Deref<Target = T>;
+    #[doc(hidden)]
+    type WriteGuard<'a, T: 'a>: DerefMut<Target = T>;
+
+    #[doc(hidden)]
+    fn new<T>(val: T) -> Self::Lock<T>;
+    #[doc(hidden)]
+    fn read<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Guarded<Self::ReadGuard<'a, T>>;
+    #[doc(hidden)]
+    fn write<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Guarded<Self::WriteGuard<'a, T>>;
+    #[doc(hidden)]
+    fn try_read<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Option<Guarded<Self::ReadGuard<'a, T>>>;
+    #[doc(hidden)]
+    fn try_write<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Option<Guarded<Self::WriteGuard<'a, T>>>;
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    fn read_for<'a, T: 'a>(
+        lock: &'a Self::Lock<T>,
+        timeout: Duration,
+    ) -> Option<Guarded<Self::ReadGuard<'a, T>>>;
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    fn write_for<'a, T: 'a>(
+        lock: &'a Self::Lock<T>,
+        timeout: Duration,
+    ) -> Option<Guarded<Self::WriteGuard<'a, T>>>;
+    #[doc(hidden)]
+    fn is_poisoned<T>(lock: &Self::Lock<T>) -> bool;
+}
+
+/// The lock backing a [Counter](struct.Counter.html) by default: A mutex, which every access holds exclusively.
+///
+/// Which mutex is selected by features, as listed in the [crate documentation](../index.html).
+/// The counter can be shared between threads if the counted value is `Send`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Exclusive;
+
+impl sealed::Sealed for Exclusive {}
+
+impl Backend for Exclusive {
+    type Lock<T> = imp::Lock<T>;
+    type ReadGuard<'a, T: 'a> = imp::ReadGuard<'a, T>;
+    type WriteGuard<'a, T: 'a> = imp::WriteGuard<'a, T>;
+
+    #[inline]
+    fn new<T>(val: T) -> Self::Lock<T> {
+        imp::new(val)
+    }
+
+    #[inline]
+    fn read<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Guarded<Self::ReadGuard<'a, T>> {
+        imp::read(lock)
+    }
+
+    #[inline]
+    fn write<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Guarded<Self::WriteGuard<'a, T>> {
+        imp::write(lock)
+    }
+
+    #[inline]
+    fn try_read<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Option<Guarded<Self::ReadGuard<'a, T>>> {
+        imp::try_read(lock)
+    }
+
+    #[inline]
+    fn try_write<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Option<Guarded<Self::WriteGuard<'a, T>>> {
+        imp::try_write(lock)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    fn read_for<'a, T: 'a>(
+        lock: &'a Self::Lock<T>,
+        timeout: Duration,
+    ) -> Option<Guarded<Self::ReadGuard<'a, T>>> {
+        imp::write_for(lock, timeout)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    fn write_for<'a, T: 'a>(
+        lock: &'a Self::Lock<T>,
+        timeout: Duration,
+    ) -> Option<Guarded<Self::WriteGuard<'a, T>>> {
+        imp::write_for(lock, timeout)
+    }
+
+    #[inline]
+    fn is_poisoned<T>(lock: &Self::Lock<T>) -> bool {
+        imp::is_poisoned(lock)
+    }
+}
+
+/// The lock backing an [RwCounter](type.RwCounter.html): A `parking_lot::RwLock`, letting concurrent `get_borrowed` and `get_cloned` calls share the lock.
+///
+/// As readers share the counted value, the counter can only be shared between threads if the counted value is `Send` and `Sync`.
+///
+/// Requires the `parking_lot_rwlock` feature.
+#[cfg(feature = "parking_lot_rwlock")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadWrite;
+
+#[cfg(feature = "parking_lot_rwlock")]
+impl sealed::Sealed for ReadWrite {}
+
+#[cfg(feature = "parking_lot_rwlock")]
+impl Backend for ReadWrite {
+    type Lock<T> = parking_lot::RwLock<T>;
+    type ReadGuard<'a, T: 'a> = parking_lot::RwLockReadGuard<'a, T>;
+    type WriteGuard<'a, T: 'a> = parking_lot::RwLockWriteGuard<'a, T>;
+
+    #[inline]
+    fn new<T>(val: T) -> Self::Lock<T> {
+        new_rw(val)
+    }
+
+    #[inline]
+    fn read<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Guarded<Self::ReadGuard<'a, T>> {
+        Ok(lock.read())
+    }
+
+    #[inline]
+    fn write<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Guarded<Self::WriteGuard<'a, T>> {
+        Ok(lock.write())
+    }
+
+    #[inline]
+    fn try_read<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Option<Guarded<Self::ReadGuard<'a, T>>> {
+        lock.try_read().map(Ok)
+    }
+
+    #[inline]
+    fn try_write<'a, T: 'a>(lock: &'a Self::Lock<T>) -> Option<Guarded<Self::WriteGuard<'a, T>>> {
+        lock.try_write().map(Ok)
+    }
+
+    #[inline]
+    fn read_for<'a, T: 'a>(
+        lock: &'a Self::Lock<T>,
+        timeout: Duration,
+    ) -> Option<Guarded<Self::ReadGuard<'a, T>>> {
+        lock.try_read_for(timeout).map(Ok)
+    }
+
+    #[inline]
+    fn write_for<'a, T: 'a>(
+        lock: &'a Self::Lock<T>,
+        timeout: Duration,
+    ) -> Option<Guarded<Self::WriteGuard<'a, T>>> {
+        lock.try_write_for(timeout).map(Ok)
+    }
+
+    #[inline]
+    fn is_poisoned<T>(_lock: &Self::Lock<T>) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "parking_lot_rwlock")]
+#[inline]
+pub const fn new_rw<T>(val: T) -> parking_lot::RwLock<T> {
+    parking_lot::const_rwlock(val)
+}
+
+#[cfg(any(feature = "spin_lock", not(feature = "std")))]
+mod imp {
+    use super::Guarded;
+
+    pub type Lock<T> = spin::Mutex<T>;
+    pub type ReadGuard<'a, T> = spin::MutexGuard<'a, T>;
+    pub type WriteGuard<'a, T> = spin::MutexGuard<'a, T>;
+
+    #[cfg(test)]
+    pub const POISONS: bool = false;
+
+    #[inline]
+    pub const fn new<T>(val: T) -> Lock<T> {
+        spin::Mutex::new(val)
+    }
+
+    #[inline]
+    pub fn read<T>(lock: &Lock<T>) -> Guarded<ReadGuard<'_, T>> {
+        Ok(lock.lock())
+    }
+
+    #[inline]
+    pub fn write<T>(lock: &Lock<T>) -> Guarded<WriteGuard<'_, T>> {
+        Ok(lock.lock())
+    }
+    #[inline]
+    pub fn try_read<T>(lock: &Lock<T>) -> Option<Guarded<ReadGuard<'_, T>>> {
+        lock.try_lock().map(Ok)
+    }
+
+    #[inline]
+    pub fn try_write<T>(lock: &Lock<T>) -> Option<Guarded<WriteGuard<'_, T>>> {
+        lock.try_lock().map(Ok)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_for<T>(
+        lock: &Lock<T>,
+        timeout: core::time::Duration,
+    ) -> Option<Guarded<WriteGuard<'_, T>>> {
+        super::retry_for(timeout, || try_write(lock))
+    }
+
+    #[inline]
+    pub fn is_poisoned<T>(_lock: &Lock<T>) -> bool {
+        false
+    }
+}
+
+#[cfg(all(feature = "parking_lot", not(feature = "spin_lock"), feature = "std"))]
+mod imp {
+    use super::Guarded;
+
+    use core::time::Duration;
+
+    pub type Lock<T> = parking_lot::Mutex<T>;
+    pub type ReadGuard<'a, T> = parking_lot::MutexGuard<'a, T>;
+    pub type WriteGuard<'a, T> = parking_lot::MutexGuard<'a, T>;
+
+    #[cfg(test)]
+    pub const POISONS: bool = false;
+
+    #[inline]
+    pub const fn new<T>(val: T) -> Lock<T> {
+        parking_lot::const_mutex(val)
+    }
+
+    #[inline]
+    pub fn read<T>(lock: &Lock<T>) -> Guarded<ReadGuard<'_, T>> {
+        Ok(lock.lock())
+    }
+
+    #[inline]
+    pub fn write<T>(lock: &Lock<T>) -> Guarded<WriteGuard<'_, T>> {
+        Ok(lock.lock())
+    }
+    #[inline]
+    pub fn try_read<T>(lock: &Lock<T>) -> Option<Guarded<ReadGuard<'_, T>>> {
+        try_write(lock)
+    }
+
+    #[inline]
+    pub fn try_write<T>(lock: &Lock<T>) -> Option<Guarded<WriteGuard<'_, T>>> {
+        lock.try_lock().map(Ok)
+    }
+
+    #[inline]
+    pub fn write_for<T>(lock: &Lock<T>, timeout: Duration) -> Option<Guarded<WriteGuard<'_, T>>> {
+        lock.try_lock_for(timeout).map(Ok)
+    }
+
+    #[inline]
+    pub fn is_poisoned<T>(_lock: &Lock<T>) -> bool {
+        false
+    }
+}
+
+#[cfg(all(
+    not(feature = "parking_lot"),
+    not(feature = "spin_lock"),
+    feature = "std"
+))]
+mod imp {
+    use super::Guarded;
+
+    use std::sync::{PoisonError, TryLockError};
+
+    pub type Lock<T> = std::sync::Mutex<T>;
+    pub type ReadGuard<'a, T> = std::sync::MutexGuard<'a, T>;
+    pub type WriteGuard<'a, T> = std::sync::MutexGuard<'a, T>;
+
+    #[cfg(test)]
+    pub const POISONS: bool = true;
+
+    #[inline]
+    pub const fn new<T>(val: T) -> Lock<T> {
+        std::sync::Mutex::new(val)
+    }
+
+    #[inline]
+    pub fn read<T>(lock: &Lock<T>) -> Guarded<ReadGuard<'_, T>> {
+        write(lock)
+    }
+
+    #[inline]
+    pub fn write<T>(lock: &Lock<T>) -> Guarded<WriteGuard<'_, T>> {
+        lock.lock().map_err(PoisonError::into_inner)
+    }
+    #[inline]
+    pub fn try_read<T>(lock: &Lock<T>) -> Option<Guarded<ReadGuard<'_, T>>> {
+        try_write(lock)
+    }
+
+    #[inline]
+    pub fn try_write<T>(lock: &Lock<T>) -> Option<Guarded<WriteGuard<'_, T>>> {
+        match lock.try_lock() {
+            Ok(guard) => Some(Ok(guard)),
+            Err(TryLockError::Poisoned(err)) => Some(Err(err.into_inner())),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    #[inline]
+    pub fn write_for<T>(
+        lock: &Lock<T>,
+        timeout: core::time::Duration,
+    ) -> Option<Guarded<WriteGuard<'_, T>>> {
+        super::retry_for(timeout, || try_write(lock))
+    }
+
+    #[inline]
+    pub fn is_poisoned<T>(lock: &Lock<T>) -> bool {
+        lock.is_poisoned()
+    }
+}
+
+/// Retries the non-blocking `attempt` until it succeeds or the timeout elapses, for locks which cannot wait with a timeout themselves.
+#[cfg(all(
+    feature = "std",
+    any(feature = "spin_lock", not(feature = "parking_lot"))
+))]
+fn retry_for<G>(timeout: Duration, mut attempt: impl FnMut() -> Option<G>) -> Option<G> {
+    // A timeout too long to represent waits forever.
+    let deadline = std::time::Instant::now().checked_add(timeout);
+    loop {
+        if let Some(guarded) = attempt() {
+            return Some(guarded);
+        }
+        if deadline.map_or(false, |deadline| std::time::Instant::now() >= deadline) {
+            return None;
+        }
+        std::thread::yield_now();
+    }
+}
+
+pub use imp::new;
+#[cfg(test)]
+pub use imp::POISONS;
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    type Lock<T> = <Exclusive as Backend>::Lock<T>;
+
+    #[test]
+    fn backend_selected() {
+        let name = core::any::type_name::<Lock<u8>>();
+        assert_eq!(POISONS, name.contains("std::sync"));
+        let expected = if cfg!(any(feature = "spin_lock", not(feature = "std"))) {
+            "spin::"
+        } else if cfg!(feature = "parking_lot") {
+            "lock_api::mutex::Mutex"
+        } else {
+            "std::sync"
+        };
+        assert!(name.contains(expected), "{} is not {}", name, expected);
+    }
+
+    #[test]
+    fn backend_read_write() {
+        static LOCK: Lock<u32> = new(1);
+        *Exclusive::write(&LOCK).unwrap() += 1;
+        {
+            // A writer on another thread has to wait until the read guard is dropped.
+            let guard = Exclusive::read(&LOCK).unwrap();
+            let t_0 = std::thread::spawn(|| *Exclusive::write(&LOCK).unwrap() += 1);
+            assert_eq!(*guard, 2);
+            drop(guard);
+            t_0.join().expect("Err joining thread");
+        }
+        assert_eq!(*Exclusive::read(&LOCK).unwrap(), 3);
+    }
+
+    #[test]
+    fn backend_try_read_write() {
+        static LOCK: Lock<u32> = new(1);
+        let guard = Exclusive::write(&LOCK).unwrap();
+        let t_0 = std::thread::spawn(|| {
+            Exclusive::try_read(&LOCK).is_none() && Exclusive::try_write(&LOCK).is_none()
+        });
+        assert!(t_0.join().expect("Err joining thread"));
+        drop(guard);
+
+        *Exclusive::try_write(&LOCK).unwrap().unwrap() += 1;
+        assert_eq!(*Exclusive::try_read(&LOCK).unwrap().unwrap(), 2);
+        assert!(!Exclusive::is_poisoned(&LOCK));
+    }
+
+    #[cfg(feature = "parking_lot_rwlock")]
+    #[test]
+    fn backend_concurrent_reads() {
+        static LOCK: parking_lot::RwLock<u32> = new_rw(5);
+        let guard = ReadWrite::read(&LOCK).unwrap();
+        // With a RwLock, another thread can read while this one holds a read guard.
+        let t_0 = std::thread::spawn(|| *ReadWrite::read(&LOCK).unwrap());
+        assert_eq!(t_0.join().expect("Err joining thread"), 5);
+        drop(guard);
+        assert!(ReadWrite::try_write(&LOCK).is_some());
+    }
+}
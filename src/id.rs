@@ -0,0 +1,235 @@
+use crate::primitive::exact::CounterU64;
+use crate::primitive::local::InstanceId;
+use std::cell::RefCell;
+use std::fmt;
+
+/// The error returned by [IdGenerator::try_next_id](struct.IdGenerator.html#method.try_next_id), if all IDs have been handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Exhausted;
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ID generator exhausted")
+    }
+}
+
+impl std::error::Error for Exhausted {}
+
+/// A generator of unique IDs.
+///
+/// An ID consists of an optional prefix in its highest bits, e.g. an epoch or a node number, and a sequence number in the remaining bits.
+/// Each thread reserves a block of sequence numbers from a global exact counter at once, and hands them out locally,
+/// so threads rarely contend on the global counter.
+///
+/// Every ID is handed out at most once, as blocks never overlap. Other than with the exact counters, the sequence numbers never wrap around:
+/// If the sequence numbers are exhausted, [try_next_id](#method.try_next_id) returns an error, and [next_id](#method.next_id) panics.
+/// The largest sequence number, all bits set, is reserved to detect exhaustion and never handed out.
+///
+/// IDs handed out by one thread are increasing, but IDs handed out by different threads are not ordered.
+/// The unused rest of a threads block is lost when the thread exits, so the sequence numbers have gaps.
+/// With a block size of one, the IDs are increasing across all threads and gapless, but every ID takes an atomic operation on the global counter.
+///
+/// # Example
+/// ```
+/// use global_counter::id::IdGenerator;
+///
+/// // Node 3 of up to 256 nodes, reserving 1024 IDs per thread at once.
+/// static IDS: IdGenerator = IdGenerator::with_prefix(3, 8, 1024);
+///
+/// fn main(){
+///     let id = IDS.next_id();
+///     assert_eq!(id >> 56, 3);
+///     assert!(IDS.next_id() > id);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct IdGenerator {
+    // The first sequence number not reserved by any thread yet.
+    next_block: CounterU64,
+    block_size: u64,
+    prefix: u64,
+    max_sequence: u64,
+    id: InstanceId,
+}
+
+thread_local! {
+    // The current block of each generator, as its instance stamp, the next sequence number to hand out and the end of the block,
+    // indexed by instance ids.
+    static BLOCKS: RefCell<Vec<(u64, u64, u64)>> = const { RefCell::new(Vec::new()) };
+}
+
+impl IdGenerator {
+    /// Creates a new ID generator without a prefix, reserving the given number of IDs per thread at once. Can be used in static contexts.
+    ///
+    /// # Panics
+    /// Panics if the block size is zero.
+    #[inline]
+    pub const fn new(block_size: u64) -> Self {
+        Self::with_prefix(0, 0, block_size)
+    }
+
+    /// Creates a new ID generator, putting the given prefix into the highest `prefix_bits` bits of each ID,
+    /// and reserving the given number of IDs per thread at once. Can be used in static contexts.
+    ///
+    /// # Panics
+    /// Panics if the prefix does not fit into `prefix_bits` bits, if `prefix_bits` is 64 or more, or if the block size is zero.
+    #[inline]
+    pub const fn with_prefix(prefix: u64, prefix_bits: u32, block_size: u64) -> Self {
+        assert!(
+            prefix_bits < 64,
+            "The prefix has to leave bits for the sequence number."
+        );
+        assert!(
+            prefix_bits > 0 || prefix == 0,
+            "The prefix does not fit into the prefix bits."
+        );
+        assert!(
+            prefix_bits == 0 || prefix >> prefix_bits == 0,
+            "The prefix does not fit into the prefix bits."
+        );
+        assert!(block_size > 0, "The block size has to be at least one.");
+        let sequence_bits = 64 - prefix_bits;
+        IdGenerator {
+            next_block: CounterU64::new(0),
+            block_size,
+            prefix: if prefix_bits == 0 {
+                0
+            } else {
+                prefix << sequence_bits
+            },
+            max_sequence: u64::MAX >> prefix_bits,
+            id: InstanceId::new(),
+        }
+    }
+
+    /// Returns a new, unique ID.
+    ///
+    /// # Panics
+    /// Panics if the sequence numbers are exhausted.
+    #[inline]
+    pub fn next_id(&self) -> u64 {
+        self.try_next_id().expect("ID generator exhausted")
+    }
+
+    /// Returns a new, unique ID, or an error if the sequence numbers are exhausted.
+    #[inline]
+    pub fn try_next_id(&self) -> Result<u64, Exhausted> {
+        let id = self.id.get();
+        let stamp = self.id.stamp();
+        let sequence = BLOCKS
+            .try_with(|blocks| {
+                let mut blocks = blocks.borrow_mut();
+                if blocks.len() <= id {
+                    blocks.resize(id + 1, (stamp, 0, 0));
+                }
+                let (block_stamp, next, end) = &mut blocks[id];
+                // The index may have belonged to a dropped generator, whose block must not be handed out.
+                if *block_stamp != stamp {
+                    *block_stamp = stamp;
+                    *end = *next;
+                }
+                if next == end {
+                    let (start, block_end) = self.reserve(self.block_size)?;
+                    *next = start;
+                    *end = block_end;
+                }
+                let sequence = *next;
+                *next += 1;
+                Ok(sequence)
+            })
+            // While the thread exits, its block may already be gone. Reserving single IDs is still correct, just slower.
+            .unwrap_or_else(|_| self.reserve(1).map(|(start, _)| start))?;
+        Ok(self.prefix | sequence)
+    }
+
+    /// Reserves a block of at most `size` sequence numbers, returning its start and end.
+    #[inline]
+    fn reserve(&self, size: u64) -> Result<(u64, u64), Exhausted> {
+        let max = self.max_sequence;
+        let start = self
+            .next_block
+            .fetch_update(|start| {
+                if start == max {
+                    None
+                } else {
+                    Some(start.saturating_add(size).min(max))
+                }
+            })
+            .map_err(|_| Exhausted)?;
+        Ok((start, start.saturating_add(size).min(max)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn id_blocks() {
+        static IDS: IdGenerator = IdGenerator::new(10);
+        assert_eq!(IDS.next_id(), 0);
+        assert_eq!(IDS.next_id(), 1);
+
+        let t_0 = std::thread::spawn(|| IDS.next_id());
+        assert_eq!(t_0.join().expect("Err joining thread"), 10);
+        for i in 2..10 {
+            assert_eq!(IDS.next_id(), i);
+        }
+        assert_eq!(IDS.next_id(), 20);
+    }
+
+    #[test]
+    fn id_prefix_exhausted() {
+        static IDS: IdGenerator = IdGenerator::with_prefix(0b101, 61, 3);
+        let ids: Vec<_> = (0..7).map(|_| IDS.try_next_id()).collect();
+        let expected: Vec<_> = (0..7).map(|i| Ok(0b101 << 3 | i)).collect();
+        assert_eq!(ids, expected);
+        assert_eq!(IDS.try_next_id(), Err(Exhausted));
+        assert_eq!(IDS.try_next_id(), Err(Exhausted));
+        let t_0 = std::thread::spawn(|| IDS.try_next_id());
+        assert_eq!(t_0.join().expect("Err joining thread"), Err(Exhausted));
+    }
+
+    #[test]
+    #[should_panic]
+    fn id_next_id_panics() {
+        static IDS: IdGenerator = IdGenerator::with_prefix(1, 63, 1);
+        IDS.next_id();
+        IDS.next_id();
+    }
+
+    #[test]
+    #[should_panic]
+    fn id_prefix_too_large() {
+        IdGenerator::with_prefix(4, 2, 1);
+    }
+
+    #[test]
+    fn id_recycled() {
+        // Every generator gets the index of the one dropped before it, but not its block.
+        for _ in 0..1000 {
+            let ids = IdGenerator::new(10);
+            assert_eq!(ids.next_id(), 0);
+            assert_eq!(ids.next_id(), 1);
+        }
+        assert!(BLOCKS.with(|blocks| blocks.borrow().len()) < 1000);
+    }
+
+    #[test]
+    fn id_unique_par_threaded() {
+        static IDS: IdGenerator = IdGenerator::new(64);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| std::thread::spawn(|| (0..10000).map(|_| IDS.next_id()).collect::<Vec<_>>()))
+            .collect();
+        let mut all = HashSet::new();
+        for t in threads {
+            let ids = t.join().expect("Err joining thread");
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+            all.extend(ids);
+        }
+        assert_eq!(all.len(), 50000);
+    }
+}
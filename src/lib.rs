@@ -4,18 +4,89 @@
 //!
 //! * [Flushing primitive counters](primitive/fast/index.html)
 //! * [Approximate primitive counters](primitive/fast/index.html)
+//! * [Sharded primitive counters](primitive/sharded/index.html)
 //! * [Exact primitive atomic counters](primitive/exact/index.html)
 //! * [Generic counter](generic/struct.Counter.html)
 //!
 //! Don't forget to make your own benchmarks, as those are very specific to the computing system in general and, in this case, to the OS in specific.
+//!
+//! Without the default `std` feature, this crate is `no_std`. Then, only the exact primitive counters are available,
+//! and the generic counter, backed by a spin lock, if the `spin_lock` feature is enabled.
+//! On targets without 64-bit atomics, like many microcontrollers, the 64-bit counters are backed by locks, just like the 128-bit ones.
+//!
+//! The mutex backing the [generic counter](generic/struct.Counter.html) is selected by features.
+//! If multiple of them are enabled, e.g. by different crates depending on this one, the first one of this list wins:
+//!
+//! * `spin_lock`: A spin lock, which never blocks the waiting thread, but keeps it busy.
+//! * `parking_lot`: A `parking_lot::Mutex`. This is the default.
+//! * `std`: The stdlibs `Mutex`.
+//!
+//! Whichever is selected, the counter can be shared between threads if the counted value is `Send`.
+//! The `parking_lot_rwlock` feature does not change this mutex, but adds the [RwCounter](generic/type.RwCounter.html),
+//! backed by a `parking_lot::RwLock`, letting concurrent `get_borrowed` and `get_cloned` calls share the lock.
+//!
+//! Every backend supports the non-blocking `try_` accessors and, with the `std` feature, waiting with a timeout, like `inc_timeout`.
+//! The parking_lot backends park the waiting thread, the others retry until the timeout elapses.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 /// This module contains a global, generic counter and the accompanying `Inc` trait.
+///
+/// Requires the `std` or the `spin_lock` feature.
+#[cfg(any(feature = "std", feature = "spin_lock"))]
 pub mod generic;
 
 /// This module contains global counters for primitive integer types.
 pub mod primitive;
 
+/// This module contains a registry, under which counters can be registered by name, to list all of them and their current values at runtime.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod registry;
+
+/// This module contains counter families, holding one counter for each unique set of label values.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod family;
+
+/// This module contains a generator of unique IDs, built on the exact primitive counters.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod id;
+
+/// This module contains primitive counters, whose values are persisted in files and survive process restarts.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod persistent;
+
+/// This module contains counters shared between processes, living in memory-mapped files.
+///
+/// Requires the `shared` feature.
+#[cfg(feature = "shared")]
+pub mod shared;
+
+/// This module contains a meter, measuring the rate at which a counter is counted.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod rate;
+
+/// This module contains an exporter, rendering all registered counters in the Prometheus text exposition format.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod prometheus;
+
+// Lock helpers ignoring poisoning.
+#[cfg(feature = "std")]
+mod sync;
+
 // Hack for macro export.
+#[cfg(feature = "std")]
 #[doc(hidden)]
 pub mod global_counter_macro_dependencies {
     pub type Lazy<T> = once_cell::sync::Lazy<T>;
@@ -0,0 +1,545 @@
+use crate::primitive::atomic::AtomicPrimitive;
+use crate::primitive::exact::AtomicCounter;
+use crate::sync;
+use once_cell::sync::OnceCell;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once, Weak};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+// Shorter intervals would keep the background thread busy, so they are rounded up to this.
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A primitive counter, whose value survives process restarts.
+///
+/// The value is stored as decimal text in a file. It is loaded from that file when the counter is first accessed,
+/// or starts from the given start value if the file does not exist yet.
+///
+/// The value is checkpointed into the file every time the given interval has passed, if the counter changed meanwhile,
+/// when [sync](#method.sync) is called, and when the counter is dropped.
+/// Counters which are never dropped, like `static`s, are checkpointed when the process exits normally, i.e. by returning from `main` or calling `std::process::exit`.
+/// This relies on `atexit` of the C standard library, so it is only done on unix and windows,
+/// and never if the process is killed, calls `std::process::abort`, or aborts on a panic, e.g. with `panic = "abort"`.
+/// Checkpoints are atomic: The value is written to a temporary file next to the counters file, which then replaces the counters file.
+///
+/// Periodic checkpoints are written by a background thread, shared by all persistent counters and started on first access,
+/// so counting never waits for the file system.
+/// If the process is killed or aborts, all changes since the last checkpoint are lost.
+/// Use an interval of zero to checkpoint on every change instead, which is durable, but slow:
+/// Every change then waits for its checkpoint to be written and synced to disk.
+///
+/// Errors of periodic checkpoints, or checkpoints on change, on drop or on exit, are ignored, as there is nobody to report them to. Call `sync` to learn about them.
+///
+/// All operations use `Ordering::SeqCst`.
+///
+/// # Example
+/// ```no_run
+/// use global_counter::persistent::PersistentCounterU64;
+/// use std::time::Duration;
+///
+/// static JOB_IDS: PersistentCounterU64 = PersistentCounterU64::new("job_ids.txt", 0, Duration::from_secs(1));
+///
+/// fn main(){
+///     let id = JOB_IDS.inc();
+///     println!("Starting job {}", id);
+///     JOB_IDS.sync().expect("Could not persist job ids");
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PersistentCounter<T: AtomicPrimitive> {
+    path: FilePath,
+    start: T,
+    interval: Duration,
+    // Shared with the background thread, which only holds on to it while checkpointing.
+    state: OnceCell<Arc<State<T>>>,
+}
+
+// Paths given in static contexts are borrowed, as a `PathBuf` cannot be created there.
+#[derive(Debug)]
+enum FilePath {
+    Static(&'static str),
+    Owned(PathBuf),
+}
+
+impl FilePath {
+    #[inline]
+    fn get(&self) -> &Path {
+        match self {
+            FilePath::Static(path) => Path::new(path),
+            FilePath::Owned(path) => path,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State<T: AtomicPrimitive> {
+    counter: AtomicCounter<T>,
+    path: PathBuf,
+    interval: Duration,
+    created: Instant,
+    // Nanoseconds since `created`, after which the next change is checkpointed.
+    next_checkpoint: AtomicU64,
+    // The value in the file, which also serializes checkpoints.
+    persisted: Mutex<T>,
+}
+
+impl<T: AtomicPrimitive + FromStr> PersistentCounter<T> {
+    /// Creates a new persistent counter, stored in the file at the given path. Can be used in static contexts.
+    ///
+    /// If the file does not exist yet, the counter starts from the given value.
+    /// Intervals shorter than one millisecond, other than zero, are rounded up to one millisecond.
+    #[inline]
+    pub const fn new(path: &'static str, start: T, interval: Duration) -> Self {
+        PersistentCounter {
+            path: FilePath::Static(path),
+            start,
+            interval: clamp_interval(interval),
+            state: OnceCell::new(),
+        }
+    }
+
+    /// Creates a new persistent counter, just like [new](#method.new), but stored in the file at the given owned path.
+    #[inline]
+    pub fn with_path(path: impl Into<PathBuf>, start: T, interval: Duration) -> Self {
+        PersistentCounter {
+            path: FilePath::Owned(path.into()),
+            start,
+            interval: clamp_interval(interval),
+            state: OnceCell::new(),
+        }
+    }
+
+    /// Returns the path of the file this counter is stored in.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        self.path.get()
+    }
+
+    /// Increments the counter by one, returning the previous value.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[inline]
+    pub fn inc(&self) -> T {
+        self.update(|counter| counter.inc())
+    }
+
+    /// Adds the given value to the counter, returning the previous value.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[inline]
+    pub fn add(&self, val: T) -> T {
+        self.update(|counter| counter.add(val))
+    }
+
+    /// Gets the current value of the counter.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[inline]
+    pub fn get(&self) -> T {
+        self.state().counter.get()
+    }
+
+    /// Sets the counter to a new value.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[inline]
+    pub fn set(&self, val: T) {
+        self.update(|counter| counter.set(val))
+    }
+
+    /// Resets the counter to zero.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[inline]
+    pub fn reset(&self) {
+        self.set(T::ZERO)
+    }
+
+    /// Resets the counter to zero, returning the previous value, in a single atomic swap.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        self.update(|counter| counter.take())
+    }
+
+    /// Checkpoints the current value into the file, unless it is already stored there.
+    ///
+    /// # Panics
+    /// Panics if this is the first access and the file exists, but cannot be read or parsed.
+    #[inline]
+    pub fn sync(&self) -> io::Result<()> {
+        match self.state.get() {
+            Some(state) => state.checkpoint(),
+            // Never accessed, so there is nothing to store.
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn update<R>(&self, f: impl FnOnce(&AtomicCounter<T>) -> R) -> R {
+        let state = self.state();
+        let res = f(&state.counter);
+        // Other intervals are left to the background thread.
+        if state.interval.is_zero() {
+            let _ = state.checkpoint();
+        }
+        res
+    }
+
+    #[inline]
+    fn state(&self) -> &State<T> {
+        self.state.get_or_init(|| {
+            let path = self.path.get();
+            let val = match fs::read_to_string(path) {
+                Ok(text) => text.trim().parse().unwrap_or_else(|_| {
+                    panic!(
+                        "Persistent counter file {} does not contain a valid value.",
+                        path.display()
+                    )
+                }),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => self.start,
+                Err(e) => panic!(
+                    "Persistent counter file {} could not be read: {}",
+                    path.display(),
+                    e
+                ),
+            };
+            let state = Arc::new(State {
+                counter: AtomicCounter::new(val),
+                path: path.to_path_buf(),
+                interval: self.interval,
+                created: Instant::now(),
+                next_checkpoint: AtomicU64::new(nanos(self.interval)),
+                persisted: Mutex::new(val),
+            });
+            register(Arc::downgrade(&state) as Weak<dyn Checkpoint>);
+            state
+        })
+    }
+}
+
+/// Checkpoints the current value, unless it is already stored in the file. Errors are ignored, call `sync` before dropping to learn about them.
+impl<T: AtomicPrimitive> Drop for PersistentCounter<T> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.get() {
+            let _ = state.checkpoint();
+        }
+    }
+}
+
+/// Rounds intervals shorter than the minimum up to it, unless they are zero.
+#[inline]
+const fn clamp_interval(interval: Duration) -> Duration {
+    if !interval.is_zero() && interval.as_nanos() < MIN_INTERVAL.as_nanos() {
+        MIN_INTERVAL
+    } else {
+        interval
+    }
+}
+
+/// Converts the duration to nanoseconds, saturating after about 584 years.
+#[inline]
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Writes the value to a temporary file next to the given path, which then replaces the file at the path.
+fn write_atomically(path: &Path, val: impl Display) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    writeln!(file, "{}", val)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+// Type erasure for the list of counters to checkpoint periodically and on exit.
+trait Checkpoint: Send + Sync {
+    fn checkpoint(&self) -> io::Result<()>;
+
+    /// Checkpoints if the interval has passed, returning the time until the next checkpoint is due, if there is one.
+    fn checkpoint_due(&self) -> Option<Duration>;
+}
+
+impl<T: AtomicPrimitive> Checkpoint for State<T> {
+    fn checkpoint(&self) -> io::Result<()> {
+        let mut persisted = sync::lock(&self.persisted);
+        let val = self.counter.get();
+        let res = if val != *persisted {
+            write_atomically(&self.path, val).map(|()| *persisted = val)
+        } else {
+            Ok(())
+        };
+        // Even a failed checkpoint waits for the next interval, so the background thread does not retry in a loop.
+        let next = nanos(self.created.elapsed()).saturating_add(nanos(self.interval));
+        self.next_checkpoint.store(next, Ordering::Relaxed);
+        res
+    }
+
+    fn checkpoint_due(&self) -> Option<Duration> {
+        if self.interval.is_zero() {
+            return None;
+        }
+        if nanos(self.created.elapsed()) >= self.next_checkpoint.load(Ordering::Relaxed) {
+            let _ = self.checkpoint();
+        }
+        let next = self.next_checkpoint.load(Ordering::Relaxed);
+        Some(Duration::from_nanos(
+            next.saturating_sub(nanos(self.created.elapsed())),
+        ))
+    }
+}
+
+// Dropped counters are only removed from this list the next time it is read.
+static COUNTERS: Mutex<Vec<Weak<dyn Checkpoint>>> = Mutex::new(Vec::new());
+
+// The background thread writing periodic checkpoints.
+static CHECKPOINTER: OnceCell<Thread> = OnceCell::new();
+
+#[inline]
+fn counters() -> MutexGuard<'static, Vec<Weak<dyn Checkpoint>>> {
+    sync::lock(&COUNTERS)
+}
+
+/// Returns all counters which are not dropped yet.
+///
+/// They are collected first, so registering counters does not wait for checkpoints to be written.
+fn live_counters() -> Vec<Arc<dyn Checkpoint>> {
+    let mut counters = counters();
+    counters.retain(|counter| counter.strong_count() > 0);
+    counters.iter().filter_map(Weak::upgrade).collect()
+}
+
+#[cfg(any(unix, windows))]
+extern "C" fn checkpoint_all() {
+    // Unwinding out of a function called from C is undefined behaviour, so a panic, which is not expected anyway, is caught here.
+    let _ = std::panic::catch_unwind(|| {
+        for counter in live_counters() {
+            let _ = counter.checkpoint();
+        }
+    });
+}
+
+/// Checkpoints all counters still alive when the process exits normally, using `atexit` of the C standard library.
+///
+/// This does not cover processes which are killed, call `std::process::abort`, or abort on a panic.
+#[cfg(any(unix, windows))]
+fn checkpoint_at_exit() {
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+    }
+    // SAFETY: `atexit` is part of the C standard library, which std links on unix and windows, and is declared with its C signature.
+    // `checkpoint_all` is a plain `extern "C"` function, which never unwinds, and only uses statics, which are never destroyed.
+    // If registering fails, counters are still checkpointed periodically, on `sync` and on drop.
+    unsafe {
+        atexit(checkpoint_all);
+    }
+}
+
+// Without a known `atexit`, counters which are never dropped are only checkpointed periodically and on `sync`.
+#[cfg(not(any(unix, windows)))]
+fn checkpoint_at_exit() {}
+
+fn checkpoint_periodically() {
+    loop {
+        let wait = live_counters()
+            .iter()
+            .filter_map(|counter| counter.checkpoint_due())
+            .min()
+            // Newly registered counters wake this thread up.
+            .unwrap_or(Duration::from_secs(3600));
+        thread::park_timeout(wait);
+    }
+}
+
+fn register(counter: Weak<dyn Checkpoint>) {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        checkpoint_at_exit();
+        // If the thread cannot be spawned, counters are still checkpointed on `sync`, on drop and on exit.
+        if let Ok(handle) = thread::Builder::new()
+            .name("global_counter checkpoints".into())
+            .spawn(checkpoint_periodically)
+        {
+            let _ = CHECKPOINTER.set(handle.thread().clone());
+        }
+    });
+    counters().push(counter);
+    if let Some(checkpointer) = CHECKPOINTER.get() {
+        checkpointer.unpark();
+    }
+}
+
+macro_rules! aliases {
+    ($( $primitive:ident $counter:ident ), *) => {
+        $(
+            #[doc = concat!("A persistent counter, counting `", stringify!($primitive), "`s.")]
+            pub type $counter = PersistentCounter<$primitive>;
+        )*
+    };
+}
+
+aliases![u8 PersistentCounterU8, u16 PersistentCounterU16, u32 PersistentCounterU32, u64 PersistentCounterU64, usize PersistentCounterUsize, u128 PersistentCounterU128, i8 PersistentCounterI8, i16 PersistentCounterI16, i32 PersistentCounterI32, i64 PersistentCounterI64, isize PersistentCounterIsize, i128 PersistentCounterI128];
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "global_counter_{}_{}.txt",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn persistent_new_const() {
+        static COUNTER: PersistentCounterU64 =
+            PersistentCounterU64::new("unused.txt", 0, Duration::from_secs(60));
+        assert_eq!(COUNTER.path(), Path::new("unused.txt"));
+    }
+
+    #[test]
+    fn persistent_survives_restart() {
+        let path = temp_path("survives_restart");
+
+        let counter = PersistentCounterU64::with_path(&path, 10, Duration::from_secs(3600));
+        assert_eq!(counter.inc(), 10);
+        assert_eq!(counter.add(5), 11);
+        // The interval has not passed yet.
+        assert!(fs::read_to_string(&path).is_err());
+        counter.sync().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "16\n");
+
+        let restarted = PersistentCounterU64::with_path(&path, 10, Duration::from_secs(3600));
+        assert_eq!(restarted.get(), 16);
+        assert_eq!(restarted.take(), 16);
+        restarted.inc();
+        restarted.reset();
+        restarted.sync().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persistent_checkpoint_on_drop() {
+        let path = temp_path("checkpoint_on_drop");
+
+        let counter = PersistentCounterI64::with_path(&path, 0, Duration::from_secs(3600));
+        counter.add(-3);
+        assert!(fs::read_to_string(&path).is_err());
+        drop(counter);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "-3\n");
+
+        // Counters which were never accessed leave the file alone.
+        drop(PersistentCounterI64::with_path(
+            &path,
+            9,
+            Duration::from_secs(3600),
+        ));
+        let restarted = PersistentCounterI64::with_path(&path, 0, Duration::from_secs(3600));
+        assert_eq!(restarted.get(), -3);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persistent_checkpoint_on_change() {
+        let path = temp_path("checkpoint_on_change");
+
+        let counter = Arc::new(PersistentCounterI32::with_path(
+            &path,
+            0,
+            Duration::from_secs(0),
+        ));
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        counter.add(-1);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "-500\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persistent_checkpoint_in_background() {
+        let path = temp_path("checkpoint_in_background");
+
+        let counter = PersistentCounterU16::with_path(&path, 0, Duration::from_millis(20));
+        // Counting does not checkpoint, the background thread does once the interval has passed.
+        counter.add(7);
+        let start = Instant::now();
+        while fs::read_to_string(&path).ok().as_deref() != Some("7\n") {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "No checkpoint in the background"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        drop(counter);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persistent_short_interval() {
+        let path = temp_path("short_interval");
+
+        let counter = PersistentCounterU32::with_path(&path, 0, Duration::from_nanos(1));
+        assert_eq!(counter.interval, MIN_INTERVAL);
+        assert_eq!(
+            PersistentCounterU32::with_path(&path, 0, Duration::from_secs(0)).interval,
+            Duration::from_secs(0)
+        );
+
+        // The next checkpoint is always at least the minimum interval away, so the background thread sleeps in between.
+        counter.inc();
+        counter.sync().unwrap();
+        let state = counter.state();
+        let due = state.next_checkpoint.load(Ordering::Relaxed);
+        assert!(due >= nanos(MIN_INTERVAL));
+        assert!(state.checkpoint_due().unwrap() <= MIN_INTERVAL);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1\n");
+
+        drop(counter);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persistent_invalid_file() {
+        let path = temp_path("invalid_file");
+        fs::write(&path, "not a number").unwrap();
+        let counter = PersistentCounterU8::with_path(&path, 0, Duration::from_secs(0));
+        let res = std::panic::catch_unwind(|| counter.inc());
+        fs::remove_file(&path).unwrap();
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,425 @@
+#[cfg(feature = "std")]
+use super::local::Locals;
+use core::cell::UnsafeCell;
+use core::fmt::{self, Debug, Display};
+use core::hash::Hash;
+use core::mem::ManuallyDrop;
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{
+    AtomicI16, AtomicI32, AtomicI8, AtomicIsize, AtomicU16, AtomicU32, AtomicU8, AtomicUsize,
+    Ordering,
+};
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::{AtomicI64, AtomicU64};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard};
+#[cfg(feature = "std")]
+use std::thread::LocalKey;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A primitive integer type, which has an atomic equivalent.
+///
+/// This trait is implemented for all primitive integer types, and is sealed, meaning it cannot be implemented outside of this crate.
+/// It allows writing code which is generic over the counted width, like
+/// `fn report<T: AtomicPrimitive>(counter: &AtomicCounter<T>)`.
+///
+/// The hidden methods map the operations of the primitive to its atomic equivalent. They are not part of the public API.
+pub trait AtomicPrimitive:
+    sealed::Sealed + Copy + Default + Debug + Display + Eq + Ord + Hash + Send + Sync + 'static
+{
+    /// The atomic equivalent of this type, e.g. `AtomicU8` for `u8`.
+    type Atomic: Debug + Default + Send + Sync + 'static;
+
+    /// The unsigned equivalent of this type, e.g. `u8` for `i8`. Used to bound magnitudes.
+    type Unsigned: AtomicPrimitive;
+
+    /// The value `0`.
+    const ZERO: Self;
+    /// The value `1`.
+    const ONE: Self;
+    /// The smallest value of this type.
+    const MIN: Self;
+    /// The largest value of this type.
+    const MAX: Self;
+
+    #[doc(hidden)]
+    fn load(atomic: &Self::Atomic, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn store(atomic: &Self::Atomic, val: Self, ordering: Ordering);
+    #[doc(hidden)]
+    fn swap(atomic: &Self::Atomic, val: Self, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn fetch_add(atomic: &Self::Atomic, val: Self, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn fetch_sub(atomic: &Self::Atomic, val: Self, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn fetch_max(atomic: &Self::Atomic, val: Self, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn fetch_min(atomic: &Self::Atomic, val: Self, ordering: Ordering) -> Self;
+    #[doc(hidden)]
+    fn compare_exchange(
+        atomic: &Self::Atomic,
+        current: Self,
+        new: Self,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self, Self>;
+    #[doc(hidden)]
+    fn fetch_update<F: FnMut(Self) -> Option<Self>>(
+        atomic: &Self::Atomic,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        f: F,
+    ) -> Result<Self, Self>;
+
+    #[doc(hidden)]
+    fn wrapping_add(self, rhs: Self) -> Self;
+    #[doc(hidden)]
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    #[doc(hidden)]
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    #[doc(hidden)]
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    #[doc(hidden)]
+    fn checked_neg(self) -> Option<Self>;
+    #[doc(hidden)]
+    fn saturating_add(self, rhs: Self) -> Self;
+    #[doc(hidden)]
+    fn saturating_sub(self, rhs: Self) -> Self;
+    /// The absolute value, as the unsigned equivalent.
+    #[doc(hidden)]
+    fn magnitude(self) -> Self::Unsigned;
+
+    /// The thread-local storage of the fast counters counting this type.
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    fn fast_locals() -> &'static LocalKey<Locals<Self::Atomic>>;
+}
+
+/// A signed primitive integer type, which has an atomic equivalent.
+///
+/// Just like [AtomicPrimitive](trait.AtomicPrimitive.html), this trait is sealed.
+pub trait SignedPrimitive: AtomicPrimitive {}
+
+// Trait methods cannot be called in const contexts, so converting a primitive into its atomic equivalent
+// in the counters `const fn`s is done by reinterpreting it.
+union Transmute<T: Copy, A> {
+    val: T,
+    atomic: ManuallyDrop<A>,
+}
+
+/// Converts a primitive into its atomic equivalent. Can be used in const contexts.
+#[inline]
+pub(crate) const fn new_atomic<T: AtomicPrimitive>(val: T) -> T::Atomic {
+    // This is safe, as every atomic integer type has the same size and bit validity as its underlying integer type.
+    unsafe { ManuallyDrop::into_inner(Transmute::<T, T::Atomic> { val }.atomic) }
+}
+
+// Std has no stable 128-bit atomics, and some targets, like 32-bit embedded ones, have no 64-bit atomics either.
+// Those are emulated using a small, global set of locks.
+// Each atomic is guarded by the lock its address hashes to, so unrelated atomics rarely contend.
+const LOCK_STRIPES: usize = 64;
+
+#[cfg(feature = "std")]
+type Stripe = Mutex<()>;
+
+#[cfg(feature = "std")]
+type StripeGuard = MutexGuard<'static, ()>;
+
+#[cfg(feature = "std")]
+#[allow(clippy::declare_interior_mutable_const)]
+const UNLOCKED: Stripe = Mutex::new(());
+
+#[cfg(feature = "std")]
+#[inline]
+fn lock_stripe(stripe: usize) -> StripeGuard {
+    crate::sync::lock(&LOCKS[stripe])
+}
+
+// Without std, there are no blocking locks, so spin locks have to do. They are held only for a single arithmetic operation.
+#[cfg(not(feature = "std"))]
+type Stripe = AtomicBool;
+
+#[cfg(not(feature = "std"))]
+struct StripeGuard(&'static AtomicBool);
+
+#[cfg(not(feature = "std"))]
+impl Drop for StripeGuard {
+    #[inline]
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[allow(clippy::declare_interior_mutable_const)]
+const UNLOCKED: Stripe = AtomicBool::new(false);
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn lock_stripe(stripe: usize) -> StripeGuard {
+    let lock = &LOCKS[stripe];
+    while lock
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    StripeGuard(lock)
+}
+
+static LOCKS: [Stripe; LOCK_STRIPES] = [UNLOCKED; LOCK_STRIPES];
+
+macro_rules! locked_atomic {
+    ($( $primitive:ident $atomic:ident ), *) => {
+        $(
+            #[doc = concat!("An integer type which can be safely shared between threads, just like the atomics of `core::sync::atomic`, holding an `", stringify!($primitive), "`.")]
+            ///
+            /// As std has no stable 128-bit atomics, and some targets have no 64-bit atomics, this type is emulated using locks:
+            /// Every operation acquires one of a fixed set of global locks, determined by the address of the atomic.
+            /// Thus, every operation is sequentially consistent, regardless of the ordering passed.
+            ///
+            /// Usually, this type is used through the [exact counters](../exact/index.html).
+            #[repr(transparent)]
+            #[derive(Default)]
+            pub struct $atomic(UnsafeCell<$primitive>);
+
+            // This is safe, as the inner value is only ever accessed while holding its lock.
+            unsafe impl Sync for $atomic {}
+
+            impl $atomic {
+                /// Creates a new atomic integer. Can be used in static contexts.
+                #[inline]
+                pub const fn new(val: $primitive) -> Self {
+                    $atomic(UnsafeCell::new(val))
+                }
+
+                /// Locks this atomic, returning a guard and a pointer to the value.
+                #[inline]
+                fn lock(&self) -> (StripeGuard, *mut $primitive) {
+                    // The lowest bits of the address are the same for all atomics, as they are aligned.
+                    let stripe = (self as *const Self as usize >> 4) % LOCK_STRIPES;
+                    (lock_stripe(stripe), self.0.get())
+                }
+
+                /// Loads the value.
+                #[inline]
+                pub fn load(&self, _ordering: Ordering) -> $primitive {
+                    let (_guard, val) = self.lock();
+                    unsafe { *val }
+                }
+
+                /// Stores a value.
+                #[inline]
+                pub fn store(&self, new: $primitive, _ordering: Ordering) {
+                    let (_guard, val) = self.lock();
+                    unsafe { *val = new }
+                }
+
+                /// Stores a value, returning the previous value.
+                #[inline]
+                pub fn swap(&self, new: $primitive, _ordering: Ordering) -> $primitive {
+                    self.fetch_update_locked(|_| Some(new)).unwrap()
+                }
+
+                /// Adds to the current value, wrapping around on overflow, returning the previous value.
+                #[inline]
+                pub fn fetch_add(&self, rhs: $primitive, _ordering: Ordering) -> $primitive {
+                    self.fetch_update_locked(|val| Some(val.wrapping_add(rhs))).unwrap()
+                }
+
+                /// Subtracts from the current value, wrapping around on overflow, returning the previous value.
+                #[inline]
+                pub fn fetch_sub(&self, rhs: $primitive, _ordering: Ordering) -> $primitive {
+                    self.fetch_update_locked(|val| Some(val.wrapping_sub(rhs))).unwrap()
+                }
+
+                /// Sets the value to the maximum of the current and the given value, returning the previous value.
+                #[inline]
+                pub fn fetch_max(&self, rhs: $primitive, _ordering: Ordering) -> $primitive {
+                    self.fetch_update_locked(|val| Some(val.max(rhs))).unwrap()
+                }
+
+                /// Sets the value to the minimum of the current and the given value, returning the previous value.
+                #[inline]
+                pub fn fetch_min(&self, rhs: $primitive, _ordering: Ordering) -> $primitive {
+                    self.fetch_update_locked(|val| Some(val.min(rhs))).unwrap()
+                }
+
+                /// Stores `new` if the current value equals `current`.
+                ///
+                /// Returns the previous value, wrapped in `Ok` if it was replaced and in `Err` otherwise.
+                #[inline]
+                pub fn compare_exchange(
+                    &self,
+                    current: $primitive,
+                    new: $primitive,
+                    _success: Ordering,
+                    _failure: Ordering,
+                ) -> Result<$primitive, $primitive> {
+                    self.fetch_update_locked(|val| if val == current { Some(new) } else { None })
+                }
+
+                /// Fetches the value, and applies `f` to it, which returns an optional new value.
+                ///
+                /// Just like with the atomics of `core::sync::atomic`, `f` may be called multiple times if the value is changed concurrently.
+                /// `f` is never called while the lock is held, so it may freely use this atomic itself.
+                #[inline]
+                pub fn fetch_update<F: FnMut($primitive) -> Option<$primitive>>(
+                    &self,
+                    _set_order: Ordering,
+                    _fetch_order: Ordering,
+                    mut f: F,
+                ) -> Result<$primitive, $primitive> {
+                    let mut prev = self.load(Ordering::SeqCst);
+                    while let Some(next) = f(prev) {
+                        match self.compare_exchange(prev, next, Ordering::SeqCst, Ordering::SeqCst) {
+                            Ok(prev) => return Ok(prev),
+                            Err(current) => prev = current,
+                        }
+                    }
+                    Err(prev)
+                }
+
+                /// Applies `f` to the value while holding the lock.
+                #[inline]
+                fn fetch_update_locked(&self, f: impl FnOnce($primitive) -> Option<$primitive>) -> Result<$primitive, $primitive> {
+                    let (_guard, val) = self.lock();
+                    unsafe {
+                        let prev = *val;
+                        match f(prev) {
+                            Some(next) => {
+                                *val = next;
+                                Ok(prev)
+                            }
+                            None => Err(prev),
+                        }
+                    }
+                }
+            }
+
+            impl Debug for $atomic {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    Debug::fmt(&self.load(Ordering::SeqCst), f)
+                }
+            }
+        )*
+    };
+}
+
+locked_atomic![u128 AtomicU128, i128 AtomicI128];
+
+// Targets without 64-bit atomics, like 32-bit microcontrollers, fall back to locks for 64-bit counters as well.
+#[cfg(not(target_has_atomic = "64"))]
+locked_atomic![u64 AtomicU64, i64 AtomicI64];
+
+macro_rules! atomic_primitive {
+    ($( $primitive:ident $atomic:ident $unsigned:ident $signedness:ident ), *) => {
+        $(
+            impl sealed::Sealed for $primitive {}
+
+            impl AtomicPrimitive for $primitive {
+                type Atomic = $atomic;
+                type Unsigned = $unsigned;
+
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+                const MIN: Self = $primitive::MIN;
+                const MAX: Self = $primitive::MAX;
+
+                #[inline]
+                fn load(atomic: &$atomic, ordering: Ordering) -> Self {
+                    atomic.load(ordering)
+                }
+                #[inline]
+                fn store(atomic: &$atomic, val: Self, ordering: Ordering) {
+                    atomic.store(val, ordering)
+                }
+                #[inline]
+                fn swap(atomic: &$atomic, val: Self, ordering: Ordering) -> Self {
+                    atomic.swap(val, ordering)
+                }
+                #[inline]
+                fn fetch_add(atomic: &$atomic, val: Self, ordering: Ordering) -> Self {
+                    atomic.fetch_add(val, ordering)
+                }
+                #[inline]
+                fn fetch_sub(atomic: &$atomic, val: Self, ordering: Ordering) -> Self {
+                    atomic.fetch_sub(val, ordering)
+                }
+                #[inline]
+                fn fetch_max(atomic: &$atomic, val: Self, ordering: Ordering) -> Self {
+                    atomic.fetch_max(val, ordering)
+                }
+                #[inline]
+                fn fetch_min(atomic: &$atomic, val: Self, ordering: Ordering) -> Self {
+                    atomic.fetch_min(val, ordering)
+                }
+                #[inline]
+                fn compare_exchange(atomic: &$atomic, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self> {
+                    atomic.compare_exchange(current, new, success, failure)
+                }
+                #[inline]
+                fn fetch_update<F: FnMut(Self) -> Option<Self>>(atomic: &$atomic, set_order: Ordering, fetch_order: Ordering, f: F) -> Result<Self, Self> {
+                    atomic.fetch_update(set_order, fetch_order, f)
+                }
+
+                #[inline]
+                fn wrapping_add(self, rhs: Self) -> Self {
+                    $primitive::wrapping_add(self, rhs)
+                }
+                #[inline]
+                fn wrapping_sub(self, rhs: Self) -> Self {
+                    $primitive::wrapping_sub(self, rhs)
+                }
+                #[inline]
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    $primitive::checked_add(self, rhs)
+                }
+                #[inline]
+                fn checked_sub(self, rhs: Self) -> Option<Self> {
+                    $primitive::checked_sub(self, rhs)
+                }
+                #[inline]
+                fn checked_neg(self) -> Option<Self> {
+                    $primitive::checked_neg(self)
+                }
+                #[inline]
+                fn saturating_add(self, rhs: Self) -> Self {
+                    $primitive::saturating_add(self, rhs)
+                }
+                #[inline]
+                fn saturating_sub(self, rhs: Self) -> Self {
+                    $primitive::saturating_sub(self, rhs)
+                }
+                #[inline]
+                fn magnitude(self) -> $unsigned {
+                    atomic_primitive!(@magnitude $signedness self)
+                }
+
+                #[cfg(feature = "std")]
+                #[inline]
+                fn fast_locals() -> &'static LocalKey<Locals<$atomic>> {
+                    // This could also be a RefCell, but this impl is also safe- or at least I hope so-
+                    // and more efficient, as no runtime borrowchecking is needed.
+                    thread_local!(static TL_COUNTERS : Locals<$atomic> = Locals::new(Vec::new()));
+                    &TL_COUNTERS
+                }
+            }
+
+            atomic_primitive!(@signed $signedness $primitive);
+        )*
+    };
+    (@magnitude unsigned $val:ident) => { $val };
+    (@magnitude signed $val:ident) => { $val.unsigned_abs() };
+    (@signed unsigned $primitive:ident) => {};
+    (@signed signed $primitive:ident) => {
+        impl SignedPrimitive for $primitive {}
+    };
+}
+
+atomic_primitive![u8 AtomicU8 u8 unsigned, u16 AtomicU16 u16 unsigned, u32 AtomicU32 u32 unsigned, u64 AtomicU64 u64 unsigned, usize AtomicUsize usize unsigned, i8 AtomicI8 u8 signed, i16 AtomicI16 u16 signed, i32 AtomicI32 u32 signed, i64 AtomicI64 u64 signed, isize AtomicIsize usize signed, u128 AtomicU128 u128 unsigned, i128 AtomicI128 u128 signed];
@@ -1,66 +1,353 @@
-use std::sync::atomic::{
-    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicU16, AtomicU32, AtomicU64,
-    AtomicU8, AtomicUsize, Ordering,
-};
+use super::atomic::{new_atomic, AtomicPrimitive};
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+/// The error returned by checked counters, if an operation would overflow the counter.
+///
+/// The counter is left unchanged in that case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("counter overflow")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Overflow {}
+
+/// Splits the given ordering into the orderings used for loads, read-modify-writes and stores, in that order.
+#[inline]
+const fn split_ordering(ordering: Ordering) -> (Ordering, Ordering, Ordering) {
+    let load_ord = match ordering {
+        Ordering::AcqRel => Ordering::Acquire,
+        other => other,
+    };
+    let store_ord = match ordering {
+        Ordering::AcqRel => Ordering::Release,
+        other => other,
+    };
+    (load_ord, ordering, store_ord)
+}
+
+/// An atomic primitive counter.
+///
+/// This counter makes all the same guarantees a generic counter does.
+/// Especially, calling `inc` N times from different threads will always result in the counter effectively being incremented by N.
+/// The counters `get` method will always return exactly the amount of times, `inc` has been called (+ start offset), up to this moment.
+///
+/// The given atomic ordering is rusts [core::sync::atomic::Ordering](https://doc.rust-lang.org/core/sync/atomic/enum.Ordering.html),
+/// with `AcqRel` translating to `AcqRel`, `Acq` or `Rel`, depending on the operation performed.
+///
+/// This counter should in general be superior in performance, compared to the equivalent generic counter.
+///
+/// Arithmetic operations wrap around on overflow. If that is not acceptable, use the equivalent saturating or checked counter.
+///
+/// Usually, this counter is used through its aliases for the primitive integer types, like [CounterU32](type.CounterU32.html).
+#[derive(Debug)]
+pub struct AtomicCounter<T: AtomicPrimitive>(T::Atomic, Ordering, Ordering, Ordering);
+
+impl<T: AtomicPrimitive> AtomicCounter<T> {
+    /// Creates a new primitive counter. Can be used in const contexts.
+    /// Uses the default `Ordering::SeqCst`, making the strongest ordering guarantees.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        AtomicCounter(
+            new_atomic(val),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+    }
+    /// Creates a new primitive counter with the given atomic ordering. Can be used in const contexts.
+    ///
+    /// Possible orderings are `Relaxed`, `AcqRel` and `SeqCst`.
+    /// Supplying an other ordering is undefined behaviour.
+    #[inline]
+    pub const fn with_ordering(val: T, ordering: Ordering) -> Self {
+        let (load_ord, rmw_ord, store_ord) = split_ordering(ordering);
+        AtomicCounter(new_atomic(val), load_ord, rmw_ord, store_ord)
+    }
+    /// Gets the current value of the counter.
+    #[inline]
+    pub fn get(&self) -> T {
+        T::load(&self.0, self.1)
+    }
+    /// Sets the counter to a new value.
+    #[inline]
+    pub fn set(&self, val: T) {
+        T::store(&self.0, val, self.3);
+    }
+    /// Increments the counter by one, returning the previous value.
+    #[inline]
+    pub fn inc(&self) -> T {
+        T::fetch_add(&self.0, T::ONE, self.2)
+    }
+    /// Resets the counter to zero.
+    #[inline]
+    pub fn reset(&self) {
+        T::store(&self.0, T::ZERO, self.3);
+    }
+    /// Resets the counter to zero, returning the previous value.
+    ///
+    /// This is a single atomic swap, so other than calling `get` and `reset`, no concurrent increment is lost in between.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        T::swap(&self.0, T::ZERO, self.2)
+    }
+    /// Adds the given value to the counter, returning the previous value.
+    ///
+    /// Just like `inc`, this wraps around on overflow.
+    #[inline]
+    pub fn add(&self, val: T) -> T {
+        T::fetch_add(&self.0, val, self.2)
+    }
+    /// Subtracts the given value from the counter, returning the previous value.
+    ///
+    /// This wraps around on overflow.
+    #[inline]
+    pub fn sub(&self, val: T) -> T {
+        T::fetch_sub(&self.0, val, self.2)
+    }
+    /// Decrements the counter by one, returning the previous value.
+    #[inline]
+    pub fn dec(&self) -> T {
+        T::fetch_sub(&self.0, T::ONE, self.2)
+    }
+    /// Sets the counter to a new value, returning the previous value.
+    #[inline]
+    pub fn swap(&self, val: T) -> T {
+        T::swap(&self.0, val, self.2)
+    }
+    /// Sets the counter to `new`, if its current value is `current`.
+    ///
+    /// Returns the previous value, wrapped in `Ok` if the counter was updated, and in `Err` otherwise.
+    #[inline]
+    pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        T::compare_exchange(&self.0, current, new, self.2, self.1)
+    }
+    /// Sets the counter to the maximum of its current value and the given value, returning the previous value.
+    #[inline]
+    pub fn fetch_max(&self, val: T) -> T {
+        T::fetch_max(&self.0, val, self.2)
+    }
+    /// Sets the counter to the minimum of its current value and the given value, returning the previous value.
+    #[inline]
+    pub fn fetch_min(&self, val: T) -> T {
+        T::fetch_min(&self.0, val, self.2)
+    }
+    /// Updates the counter with the value returned by `f`, retrying if another thread changed the counter in the meantime.
+    /// The counter is left unchanged if `f` returns `None`.
+    ///
+    /// Returns the previous value, wrapped in `Ok` if the counter was updated, and in `Err` otherwise.
+    ///
+    /// Note that `f` may be called multiple times.
+    #[inline]
+    pub fn fetch_update<F: FnMut(T) -> Option<T>>(&self, f: F) -> Result<T, T> {
+        T::fetch_update(&self.0, self.2, self.1, f)
+    }
+}
+
+/// An atomic primitive counter, which saturates instead of overflowing.
+///
+/// This counter behaves like the equivalent wrapping counter, except that all arithmetic operations stop at the
+/// minimum and maximum value of the counted type, instead of wrapping around.
+/// This is implemented using compare-and-swap loops, so it is slower than the wrapping counter under contention.
+///
+/// Orderings are handled just like by the wrapping counter.
+#[derive(Debug)]
+pub struct SaturatingCounter<T: AtomicPrimitive>(T::Atomic, Ordering, Ordering, Ordering);
+
+impl<T: AtomicPrimitive> SaturatingCounter<T> {
+    /// Creates a new saturating counter. Can be used in const contexts.
+    /// Uses the default `Ordering::SeqCst`, making the strongest ordering guarantees.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        SaturatingCounter(
+            new_atomic(val),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+    }
+    /// Creates a new saturating counter with the given atomic ordering. Can be used in const contexts.
+    ///
+    /// Possible orderings are `Relaxed`, `AcqRel` and `SeqCst`.
+    /// Supplying an other ordering is undefined behaviour.
+    #[inline]
+    pub const fn with_ordering(val: T, ordering: Ordering) -> Self {
+        let (load_ord, rmw_ord, store_ord) = split_ordering(ordering);
+        SaturatingCounter(new_atomic(val), load_ord, rmw_ord, store_ord)
+    }
+    /// Gets the current value of the counter.
+    #[inline]
+    pub fn get(&self) -> T {
+        T::load(&self.0, self.1)
+    }
+    /// Sets the counter to a new value.
+    #[inline]
+    pub fn set(&self, val: T) {
+        T::store(&self.0, val, self.3);
+    }
+    /// Resets the counter to zero.
+    #[inline]
+    pub fn reset(&self) {
+        T::store(&self.0, T::ZERO, self.3);
+    }
+    /// Resets the counter to zero, returning the previous value.
+    ///
+    /// This is a single atomic swap, so other than calling `get` and `reset`, no concurrent increment is lost in between.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        T::swap(&self.0, T::ZERO, self.2)
+    }
+    /// Increments the counter by one, saturating at the maximum value. Returns the previous value.
+    #[inline]
+    pub fn inc(&self) -> T {
+        self.add(T::ONE)
+    }
+    /// Decrements the counter by one, saturating at the minimum value. Returns the previous value.
+    #[inline]
+    pub fn dec(&self) -> T {
+        self.sub(T::ONE)
+    }
+    /// Adds the given value to the counter, saturating at the numeric bounds. Returns the previous value.
+    #[inline]
+    pub fn add(&self, val: T) -> T {
+        self.update(|prev| prev.saturating_add(val))
+    }
+    /// Subtracts the given value from the counter, saturating at the numeric bounds. Returns the previous value.
+    #[inline]
+    pub fn sub(&self, val: T) -> T {
+        self.update(|prev| prev.saturating_sub(val))
+    }
+    #[inline]
+    fn update(&self, f: impl Fn(T) -> T) -> T {
+        match T::fetch_update(&self.0, self.2, self.1, |prev| Some(f(prev))) {
+            Ok(prev) | Err(prev) => prev,
+        }
+    }
+}
 
-macro_rules! primitive_counter {
-    ($( $primitive:ident $atomic:ident $counter:ident ), *) => {
+/// An atomic primitive counter, which refuses to overflow.
+///
+/// This counter behaves like the equivalent wrapping counter, except that all arithmetic operations return an [Overflow](struct.Overflow.html) error,
+/// leaving the counter unchanged, if they would leave the numeric bounds of the counted type.
+/// This is implemented using compare-and-swap loops, so it is slower than the wrapping counter under contention.
+///
+/// Orderings are handled just like by the wrapping counter.
+#[derive(Debug)]
+pub struct CheckedCounter<T: AtomicPrimitive>(T::Atomic, Ordering, Ordering, Ordering);
+
+impl<T: AtomicPrimitive> CheckedCounter<T> {
+    /// Creates a new checked counter. Can be used in const contexts.
+    /// Uses the default `Ordering::SeqCst`, making the strongest ordering guarantees.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        CheckedCounter(
+            new_atomic(val),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+    }
+    /// Creates a new checked counter with the given atomic ordering. Can be used in const contexts.
+    ///
+    /// Possible orderings are `Relaxed`, `AcqRel` and `SeqCst`.
+    /// Supplying an other ordering is undefined behaviour.
+    #[inline]
+    pub const fn with_ordering(val: T, ordering: Ordering) -> Self {
+        let (load_ord, rmw_ord, store_ord) = split_ordering(ordering);
+        CheckedCounter(new_atomic(val), load_ord, rmw_ord, store_ord)
+    }
+    /// Gets the current value of the counter.
+    #[inline]
+    pub fn get(&self) -> T {
+        T::load(&self.0, self.1)
+    }
+    /// Sets the counter to a new value.
+    #[inline]
+    pub fn set(&self, val: T) {
+        T::store(&self.0, val, self.3);
+    }
+    /// Resets the counter to zero.
+    #[inline]
+    pub fn reset(&self) {
+        T::store(&self.0, T::ZERO, self.3);
+    }
+    /// Resets the counter to zero, returning the previous value.
+    ///
+    /// This is a single atomic swap, so other than calling `get` and `reset`, no concurrent increment is lost in between.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        T::swap(&self.0, T::ZERO, self.2)
+    }
+    /// Increments the counter by one, returning the previous value, or an error if the counter is already at its maximum value.
+    #[inline]
+    pub fn inc(&self) -> Result<T, Overflow> {
+        self.add(T::ONE)
+    }
+    /// Decrements the counter by one, returning the previous value, or an error if the counter is already at its minimum value.
+    #[inline]
+    pub fn dec(&self) -> Result<T, Overflow> {
+        self.sub(T::ONE)
+    }
+    /// Adds the given value to the counter, returning the previous value, or an error if the result would overflow.
+    #[inline]
+    pub fn add(&self, val: T) -> Result<T, Overflow> {
+        T::fetch_update(&self.0, self.2, self.1, |prev| prev.checked_add(val)).map_err(|_| Overflow)
+    }
+    /// Subtracts the given value from the counter, returning the previous value, or an error if the result would overflow.
+    #[inline]
+    pub fn sub(&self, val: T) -> Result<T, Overflow> {
+        T::fetch_update(&self.0, self.2, self.1, |prev| prev.checked_sub(val)).map_err(|_| Overflow)
+    }
+}
+
+macro_rules! aliases {
+    ($( $primitive:ident $counter:ident $saturating:ident $checked:ident ), *) => {
         $(
-            /// An atomic primitive counter.
-            ///
-            /// This counter makes all the same guarantees a generic counter does.
-            /// Especially, calling `inc` N times from different threads will always result in the counter effectively being incremented by N.
-            /// The counters `get` method will always return exactly the amount of times, `inc` has been called (+ start offset), up to this moment.
-            ///
-            /// The given atomic ordering is rusts [core::sync::atomic::Ordering](https://doc.rust-lang.org/core/sync/atomic/enum.Ordering.html),
-            /// with `AcqRel` translating to `AcqRel`, `Acq` or `Rel`, depending on the operation performed.
-            ///
-            /// This counter should in general be superior in performance, compared to the equivalent generic counter.
-            #[derive(Debug)]
-            pub struct $counter($atomic, Ordering, Ordering, Ordering);
-            impl $counter{
-                /// Creates a new primitive counter. Can be used in const contexts.
-                /// Uses the default `Ordering::SeqCst`, making the strongest ordering guarantees.
-                #[inline]
-                pub const fn new(val : $primitive) -> $counter{
-                    $counter($atomic::new(val), Ordering::SeqCst, Ordering::SeqCst, Ordering::SeqCst)
-                }
-                /// Creates a new primitive counter with the given atomic ordering. Can be used in const contexts.
-                ///
-                /// Possible orderings are `Relaxed`, `AcqRel` and `SeqCst`.
-                /// Supplying an other ordering is undefined behaviour.
-                #[inline]
-                pub const fn with_ordering(val : $primitive, ordering : Ordering) -> $counter{
-                    let store_ord = match ordering{ Ordering::AcqRel => Ordering::Release, other => other };
-                    let load_ord = match ordering{ Ordering::AcqRel => Ordering::Acquire, other => other };
-                    $counter($atomic::new(val), load_ord, ordering, store_ord)
-                }
-                /// Gets the current value of the counter.
-                #[inline]
-                pub fn get(&self) -> $primitive{
-                    self.0.load(self.1)
-                }
-                /// Sets the counter to a new value.
-                #[inline]
-                pub fn set(&self, val : $primitive){
-                    self.0.store(val, self.3);
-                }
-                /// Increments the counter by one, returning the previous value.
+            #[doc = concat!("An atomic primitive counter, counting `", stringify!($primitive), "`s.")]
+            pub type $counter = AtomicCounter<$primitive>;
+            #[doc = concat!("A saturating atomic primitive counter, counting `", stringify!($primitive), "`s.")]
+            pub type $saturating = SaturatingCounter<$primitive>;
+            #[doc = concat!("A checked atomic primitive counter, counting `", stringify!($primitive), "`s.")]
+            pub type $checked = CheckedCounter<$primitive>;
+        )*
+    };
+}
+
+aliases![u8 CounterU8 SaturatingCounterU8 CheckedCounterU8, u16 CounterU16 SaturatingCounterU16 CheckedCounterU16, u32 CounterU32 SaturatingCounterU32 CheckedCounterU32, u64 CounterU64 SaturatingCounterU64 CheckedCounterU64, usize CounterUsize SaturatingCounterUsize CheckedCounterUsize, i8 CounterI8 SaturatingCounterI8 CheckedCounterI8, i16 CounterI16 SaturatingCounterI16 CheckedCounterI16, i32 CounterI32 SaturatingCounterI32 CheckedCounterI32, i64 CounterI64 SaturatingCounterI64 CheckedCounterI64, isize CounterIsize SaturatingCounterIsize CheckedCounterIsize, u128 CounterU128 SaturatingCounterU128 CheckedCounterU128, i128 CounterI128 SaturatingCounterI128 CheckedCounterI128];
+
+#[cfg(feature = "serde")]
+macro_rules! serde_impls {
+    ($( $counter:ident ), *) => {
+        $(
+            /// Serializes the current value of the counter.
+            impl<T: AtomicPrimitive + serde::Serialize> serde::Serialize for $counter<T> {
                 #[inline]
-                pub fn inc(&self) -> $primitive{
-                    self.0.fetch_add(1, self.2)
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    self.get().serialize(serializer)
                 }
-                /// Resets the counter to zero.
+            }
+
+            /// Deserializes a value into a new counter, starting from that value, with the default ordering.
+            impl<'de, T: AtomicPrimitive + serde::Deserialize<'de>> serde::Deserialize<'de> for $counter<T> {
                 #[inline]
-                pub fn reset(&self){
-                    self.0.store(0, self.3);
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    T::deserialize(deserializer).map(Self::new)
                 }
             }
         )*
     };
 }
 
-primitive_counter![u8 AtomicU8 CounterU8, u16 AtomicU16 CounterU16, u32 AtomicU32 CounterU32, u64 AtomicU64 CounterU64, usize AtomicUsize CounterUsize, i8 AtomicI8 CounterI8, i16 AtomicI16 CounterI16, i32 AtomicI32 CounterI32, i64 AtomicI64 CounterI64, isize AtomicIsize CounterIsize];
+#[cfg(feature = "serde")]
+serde_impls![AtomicCounter, SaturatingCounter, CheckedCounter];
 
 #[cfg(test)]
 mod tests {
@@ -136,6 +423,264 @@ mod tests {
         assert_eq!(COUNTER.get(), 0);
     }
 
+    #[test]
+    fn primitive_take() {
+        static COUNTER: CounterI8 = CounterI8::new(-3);
+        assert_eq!(COUNTER.take(), -3);
+        assert_eq!(COUNTER.get(), 0);
+        COUNTER.inc();
+        assert_eq!(COUNTER.take(), 1);
+
+        static SATURATING: SaturatingCounterU8 = SaturatingCounterU8::new(u8::MAX);
+        assert_eq!(SATURATING.take(), u8::MAX);
+        assert_eq!(SATURATING.inc(), 0);
+
+        static CHECKED: CheckedCounterU128 = CheckedCounterU128::new(u128::MAX);
+        assert_eq!(CHECKED.take(), u128::MAX);
+        assert_eq!(CHECKED.inc(), Ok(0));
+    }
+
+    #[test]
+    fn primitive_take_par_threaded() {
+        static COUNTER: CounterU64 = CounterU64::new(0);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        COUNTER.inc();
+                    }
+                })
+            })
+            .collect();
+        // Taking while the threads count never loses an increment.
+        let mut taken = 0;
+        while threads.iter().any(|t| !t.is_finished()) {
+            taken += COUNTER.take();
+        }
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        taken += COUNTER.take();
+
+        assert_eq!(taken, 50000);
+    }
+
+    #[test]
+    fn primitive_add_sub_dec() {
+        static COUNTER: CounterI32 = CounterI32::new(0);
+        assert_eq!(COUNTER.add(10), 0);
+        assert_eq!(COUNTER.get(), 10);
+        assert_eq!(COUNTER.sub(15), 10);
+        assert_eq!(COUNTER.get(), -5);
+        assert_eq!(COUNTER.dec(), -5);
+        assert_eq!(COUNTER.get(), -6);
+
+        static COUNTER_2: CounterU8 = CounterU8::new(0);
+        assert_eq!(COUNTER_2.dec(), 0);
+        assert_eq!(COUNTER_2.get(), u8::MAX);
+    }
+
+    #[test]
+    fn primitive_swap_compare_exchange() {
+        static COUNTER: CounterU64 = CounterU64::with_ordering(3, Ordering::AcqRel);
+        assert_eq!(COUNTER.swap(7), 3);
+        assert_eq!(COUNTER.get(), 7);
+        assert_eq!(COUNTER.compare_exchange(3, 9), Err(7));
+        assert_eq!(COUNTER.get(), 7);
+        assert_eq!(COUNTER.compare_exchange(7, 9), Ok(7));
+        assert_eq!(COUNTER.get(), 9);
+    }
+
+    #[test]
+    fn primitive_fetch_max_min() {
+        static COUNTER: CounterUsize = CounterUsize::with_ordering(5, Ordering::Relaxed);
+        assert_eq!(COUNTER.fetch_max(3), 5);
+        assert_eq!(COUNTER.get(), 5);
+        assert_eq!(COUNTER.fetch_max(8), 5);
+        assert_eq!(COUNTER.get(), 8);
+        assert_eq!(COUNTER.fetch_min(2), 8);
+        assert_eq!(COUNTER.get(), 2);
+    }
+
+    #[test]
+    fn primitive_fetch_update() {
+        static COUNTER: CounterI16 = CounterI16::new(1);
+        assert_eq!(COUNTER.fetch_update(|val| Some(val * 10)), Ok(1));
+        assert_eq!(COUNTER.get(), 10);
+        assert_eq!(COUNTER.fetch_update(|val| val.checked_add(i16::MAX)), Err(10));
+        assert_eq!(COUNTER.get(), 10);
+    }
+
+    #[test]
+    fn primitive_128_bit() {
+        static COUNTER: CounterU128 = CounterU128::new(u64::MAX as u128);
+        assert_eq!(COUNTER.inc(), u64::MAX as u128);
+        assert_eq!(COUNTER.get(), 1 << 64);
+        assert_eq!(COUNTER.compare_exchange(1 << 64, u128::MAX), Ok(1 << 64));
+        COUNTER.inc();
+        assert_eq!(COUNTER.get(), 0);
+
+        static COUNTER_2: SaturatingCounterI128 = SaturatingCounterI128::new(i128::MIN + 1);
+        COUNTER_2.sub(5);
+        assert_eq!(COUNTER_2.get(), i128::MIN);
+    }
+
+    #[test]
+    fn primitive_128_bit_par_threaded() {
+        static COUNTER: CounterI128 = CounterI128::new(0);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        COUNTER.add(1 << 100);
+                        COUNTER.dec();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        assert_eq!(COUNTER.get(), 50000 * ((1 << 100) - 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn primitive_serde() {
+        static COUNTER: CounterI64 = CounterI64::new(-4);
+        COUNTER.inc();
+        let json = serde_json::to_string(&COUNTER).unwrap();
+        assert_eq!(json, "-3");
+
+        let restored: CounterI64 = serde_json::from_str(&json).unwrap();
+        restored.inc();
+        assert_eq!(restored.get(), -2);
+
+        let checked: CheckedCounterU8 = serde_json::from_str("255").unwrap();
+        assert_eq!(checked.inc(), Err(Overflow));
+        assert!(serde_json::from_str::<SaturatingCounterU8>("256").is_err());
+    }
+
+    #[test]
+    fn high_water_mark_par_threaded() {
+        static IN_FLIGHT: CounterI64 = CounterI64::new(0);
+        static HIGH_WATER_MARK: CounterI64 = CounterI64::new(0);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        let prev = IN_FLIGHT.inc();
+                        HIGH_WATER_MARK.fetch_max(prev + 1);
+                        IN_FLIGHT.dec();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        assert_eq!(IN_FLIGHT.get(), 0);
+        assert!(1 <= HIGH_WATER_MARK.get() && HIGH_WATER_MARK.get() <= 5);
+    }
+
+    #[test]
+    fn saturating_bounds() {
+        static COUNTER: SaturatingCounterU8 = SaturatingCounterU8::new(250);
+        for _ in 0..10 {
+            COUNTER.inc();
+        }
+        assert_eq!(COUNTER.get(), u8::MAX);
+        assert_eq!(COUNTER.inc(), u8::MAX);
+        assert_eq!(COUNTER.sub(100), u8::MAX);
+        assert_eq!(COUNTER.get(), 155);
+        assert_eq!(COUNTER.sub(200), 155);
+        assert_eq!(COUNTER.get(), 0);
+        assert_eq!(COUNTER.dec(), 0);
+        assert_eq!(COUNTER.get(), 0);
+
+        static COUNTER_2: SaturatingCounterI8 = SaturatingCounterI8::with_ordering(0, Ordering::AcqRel);
+        COUNTER_2.add(-100);
+        COUNTER_2.add(-100);
+        assert_eq!(COUNTER_2.get(), i8::MIN);
+        COUNTER_2.set(100);
+        COUNTER_2.add(100);
+        assert_eq!(COUNTER_2.get(), i8::MAX);
+        COUNTER_2.reset();
+        assert_eq!(COUNTER_2.get(), 0);
+    }
+
+    #[test]
+    fn saturating_par_threaded() {
+        static COUNTER: SaturatingCounterU16 = SaturatingCounterU16::new(0);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..20000 {
+                        COUNTER.inc();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        assert_eq!(COUNTER.get(), u16::MAX);
+    }
+
+    #[test]
+    fn checked_bounds() {
+        static RETRY_BUDGET: CheckedCounterU8 = CheckedCounterU8::new(3);
+        assert_eq!(RETRY_BUDGET.dec(), Ok(3));
+        assert_eq!(RETRY_BUDGET.dec(), Ok(2));
+        assert_eq!(RETRY_BUDGET.dec(), Ok(1));
+        assert_eq!(RETRY_BUDGET.dec(), Err(Overflow));
+        assert_eq!(RETRY_BUDGET.get(), 0);
+
+        RETRY_BUDGET.set(u8::MAX - 1);
+        assert_eq!(RETRY_BUDGET.inc(), Ok(u8::MAX - 1));
+        assert_eq!(RETRY_BUDGET.inc(), Err(Overflow));
+        assert_eq!(RETRY_BUDGET.add(0), Ok(u8::MAX));
+        assert_eq!(RETRY_BUDGET.sub(u8::MAX), Ok(u8::MAX));
+        RETRY_BUDGET.reset();
+        assert_eq!(RETRY_BUDGET.get(), 0);
+
+        static COUNTER: CheckedCounterI64 = CheckedCounterI64::new(i64::MIN + 1);
+        assert_eq!(COUNTER.sub(2), Err(Overflow));
+        assert_eq!(COUNTER.add(-1), Ok(i64::MIN + 1));
+        assert_eq!(COUNTER.get(), i64::MIN);
+    }
+
+    #[test]
+    fn checked_par_threaded() {
+        static COUNTER: CheckedCounterU16 = CheckedCounterU16::new(0);
+        static OVERFLOWS: CounterU32 = CounterU32::new(0);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..20000 {
+                        if COUNTER.inc().is_err() {
+                            OVERFLOWS.inc();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        assert_eq!(COUNTER.get(), u16::MAX);
+        assert_eq!(OVERFLOWS.get(), 100000 - u16::MAX as u32);
+    }
+
     #[test]
     fn count_to_five_single_threaded() {
         static COUNTER: CounterU32 = CounterU32::new(0);
@@ -1,176 +1,369 @@
-use core::cell::UnsafeCell;
-use core::sync::atomic::{
-    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicU16, AtomicU32, AtomicU64,
-    AtomicU8, AtomicUsize, Ordering,
-};
-use std::thread::LocalKey;
-
-macro_rules! flushing_counter {
-    ($( $primitive:ident $atomic:ident $counter:ident ), *) => {
-        $(
-            /// A flushing counter.
-            ///
-            /// This counter is intended to be used in one specific way:
-            /// * First, all counting threads increment the counter.
-            /// * Every counting thread calls `flush` after it is done incrementing.
-            /// * After every flush is guaranteed to have been executed, `get` will return the exact amount of times `inc` has been called (+ the start offset).
-            ///
-            /// In theory, this counter is equivalent to an approximate counter with its resolution set to infinity.
-            ///
-            /// Note that this counters `inc` - `flush` - `get` - path does not induce a happens-before relationship,
-            /// as defined by the C++ standard.
-            /// In other words, it is not safe to rely solely on this counter for the synchronization of external data.
-            /// The most important example of this is spinning until a certain value is reached,
-            /// which does not guarantee _any_ other operations to be visible to the reading thread.
-            pub struct $counter {
-                global_counter: $atomic,
-
-                // This could also be a RefCell, but this impl is also safe- or at least I hope so-
-                // and more efficient, as no runtime borrowchecking is needed.
-                thread_local_counter: &'static LocalKey<UnsafeCell<$primitive>>,
-            }
-
-            impl $counter {
-                /// Creates a new counter, with the given starting value. Can be used in static contexts.
-                #[inline]
-                pub const fn new(start: $primitive) -> Self {
-                    thread_local!(pub static TL_COUNTER : UnsafeCell<$primitive> = UnsafeCell::new(0));
-                    $counter {
-                        global_counter: $atomic::new(start),
-                        thread_local_counter: &TL_COUNTER,
-                    }
-                }
-
-                /// Increments the counter by one.
-                #[inline]
-                pub fn inc(&self) {
-                    self.thread_local_counter.with(|tlc| unsafe {
-                        // This is safe, because concurrent accesses to a thread-local are obviously not possible,
-                        // and aliasing is not possible using the counters API.
-                        let tlc = &mut *tlc.get();
-                        *tlc += 1;
-                    });
-                }
-
-                /// Gets the current value of the counter. This only returns the correct value after all local counters have been flushed.
-                #[inline]
-                pub fn get(&self) -> $primitive {
-                    self.global_counter.load(Ordering::Relaxed)
-                }
-
-                /// Flushes the local counter to the global.
-                #[inline]
-                pub fn flush(&self) {
-                    self.thread_local_counter.with(|tlc| unsafe {
-                        let tlc = &mut *tlc.get();
-                        self.global_counter.fetch_add(*tlc, Ordering::Relaxed);
-                        *tlc = 0;
-                    });
-                }
+use super::atomic::{new_atomic, AtomicPrimitive, SignedPrimitive};
+use super::local::{with_local, FlushFn, InstanceId, Shared};
+use core::sync::atomic::Ordering;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// A flushing counter.
+///
+/// This counter is intended to be used in one specific way:
+/// * First, all counting threads increment the counter.
+/// * Every counting thread calls `flush` after it is done incrementing, or exits, which flushes automatically.
+/// * After every flush is guaranteed to have been executed, `get` will return the exact amount of times `inc` has been called (+ the start offset).
+///
+/// In theory, this counter is equivalent to an approximate counter with its resolution set to infinity.
+///
+/// Exiting threads flush into the counter, even if it does not live for the rest of the program, as their local counters share ownership of its state.
+///
+/// Local counters never overflow: If adding to the local counter of a thread would overflow it, it is flushed first.
+/// The global counter wraps around on overflow, just like the exact primitive counters do.
+///
+/// Note that this counters `inc` - `flush` - `get` - path does not induce a happens-before relationship,
+/// as defined by the C++ standard.
+/// In other words, it is not safe to rely solely on this counter for the synchronization of external data.
+/// The most important example of this is spinning until a certain value is reached,
+/// which does not guarantee _any_ other operations to be visible to the reading thread.
+///
+/// Usually, this counter is used through its aliases for the primitive integer types, like [FlushingCounterU32](type.FlushingCounterU32.html).
+#[derive(Debug)]
+pub struct FlushingCounter<T: AtomicPrimitive> {
+    start: T,
+    id: InstanceId,
+    shared: OnceCell<Arc<Shared<T::Atomic>>>,
+}
+
+impl<T: AtomicPrimitive> FlushingCounter<T> {
+    /// Creates a new counter, with the given starting value. Can be used in static contexts.
+    #[inline]
+    pub const fn new(start: T) -> Self {
+        FlushingCounter {
+            start,
+            id: InstanceId::new(),
+            shared: OnceCell::new(),
+        }
+    }
+
+    /// Increments the counter by one.
+    #[inline]
+    pub fn inc(&self) {
+        self.with_local(|tlc| tlc.add(T::ONE));
+    }
+
+    /// Adds the given value to the counter.
+    #[inline]
+    pub fn add(&self, val: T) {
+        self.with_local(|tlc| tlc.add(val));
+    }
+
+    /// Gets the current value of the counter. This only returns the correct value after all local counters have been flushed.
+    #[inline]
+    pub fn get(&self) -> T {
+        T::load(&self.shared().global, Ordering::Relaxed)
+    }
+
+    /// Gets the exact value of the counter, adding up the global counter and the local counters of all live threads.
+    ///
+    /// This is a lot slower than `get`, as it locks the list of local counters and visits each of them, but no thread has to flush.
+    /// If other threads are counting at the same time, the result is only a snapshot: It may miss their latest increments,
+    /// including an amount being flushed right now, but never counts anything twice. Once no thread is counting, it is exact.
+    #[inline]
+    pub fn get_exact(&self) -> T {
+        get_exact::<T>(self.shared())
+    }
+
+    /// Flushes the local counter to the global.
+    #[inline]
+    pub fn flush(&self) {
+        self.with_local(LocalValue::flush);
+    }
+
+    /// Flushes the local counter to the global, then resets the global counter to zero, returning its previous value.
+    ///
+    /// Just like `get`, this does not include the local counters of other threads. Those are not lost though,
+    /// but taken by a later call to `take`, once their threads have flushed or exited.
+    /// As the global counter is swapped out atomically, every flushed increment is taken exactly once.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        self.flush();
+        T::swap(&self.shared().global, T::ZERO, Ordering::Relaxed)
+    }
+
+    /// Runs `f` on the local counter of the current thread belonging to this instance.
+    #[inline]
+    fn with_local<R>(&self, f: impl FnOnce(&mut LocalValue<T>) -> R) -> R {
+        with_local_value(self.shared(), &self.id, f)
+    }
+
+    #[inline]
+    fn shared(&self) -> &Arc<Shared<T::Atomic>> {
+        self.shared
+            .get_or_init(|| Arc::new(Shared::new(new_atomic(self.start))))
+    }
+}
+
+impl<T: SignedPrimitive> FlushingCounter<T> {
+    /// Decrements the counter by one.
+    #[inline]
+    pub fn dec(&self) {
+        self.with_local(|tlc| tlc.sub(T::ONE));
+    }
+
+    /// Subtracts the given value from the counter.
+    #[inline]
+    pub fn sub(&self, val: T) {
+        self.with_local(|tlc| tlc.sub(val));
+    }
+}
+
+/// A global approximate counter.
+///
+/// This counter operates by having a local counter for each thread, which is occasionally flushed to the main global counter.
+///
+/// The accuracy of the counter is determined by its `resolution` and the number of threads counting on it:
+/// The value returned by `get` is guaranteed to always be less than or to equal this number of threads multiplied with the resolution minus one
+/// away from the actual count, meaning the sum of all values added using `inc`, `add` and friends (+ start offset):
+///
+/// `|get - (actual + start)| <= num_threads * (resolution - 1)`
+///
+/// With resolution being >= 1. This is the only guarantee made.
+///
+/// Setting the resolution to 0 or 1 will just make it a worse primitive counter, don't do that. Increasing the resolution increases this counters performance.
+///
+/// This counter also features a `flush` method, which can be used to manually flush the local counter of the current thread.
+/// Local counters are flushed automatically when their thread exits, even if the counter does not live for the rest of the program.
+///
+/// Local counters never overflow: If adding to the local counter of a thread would overflow it, it is flushed first,
+/// regardless of the resolution. The global counter wraps around on overflow, just like the exact primitive counters do.
+///
+/// Note that this counters `inc` - (`flush`) - `get` - path does not induce a happens-before relationship, just like the flushing counters.
+///
+/// Usually, this counter is used through its aliases for the primitive integer types, like [ApproxCounterU32](type.ApproxCounterU32.html).
+#[derive(Debug)]
+pub struct ApproxCounter<T: AtomicPrimitive> {
+    // Always making the resolution unsigned was a deliberate choice.
+    // The resolution is used to upper-bound an absolute value. It cannot be negative.
+    threshold: T::Unsigned,
+    start: T,
+    id: InstanceId,
+    shared: OnceCell<Arc<Shared<T::Atomic>>>,
+}
+
+impl<T: AtomicPrimitive> ApproxCounter<T> {
+    /// Creates a new counter, with the given start value and resolution. Can be used in static contexts.
+    ///
+    /// The start value is a lower bound for the value returned by `get`, not guaranteed to be the exact value on subsequent calls.
+    #[inline]
+    pub const fn new(start: T, resolution: T::Unsigned) -> Self {
+        ApproxCounter {
+            threshold: resolution,
+            start,
+            id: InstanceId::new(),
+            shared: OnceCell::new(),
+        }
+    }
+
+    /// Increments the counter by one.
+    ///
+    /// Note that this call will probably leave the value returned by `get` unchanged.
+    #[inline]
+    pub fn inc(&self) {
+        self.update_local(|tlc| tlc.add(T::ONE));
+    }
+
+    /// Adds the given value to the counter.
+    ///
+    /// Just like `inc`, this call will probably leave the value returned by `get` unchanged.
+    #[inline]
+    pub fn add(&self, val: T) {
+        self.update_local(|tlc| tlc.add(val));
+    }
+
+    /// Gets the current value of the counter. For more information, see the struct-level documentation.
+    ///
+    /// Especially note, that two calls to `get` with one `inc` interleaved are not guaranteed to, and almost certainely wont, return different values.
+    #[inline]
+    pub fn get(&self) -> T {
+        T::load(&self.shared().global, Ordering::Relaxed)
+    }
+
+    /// Gets the exact value of the counter, adding up the global counter and the local counters of all live threads.
+    ///
+    /// This is a lot slower than `get`, as it locks the list of local counters and visits each of them.
+    /// If other threads are counting at the same time, the result is only a snapshot: It may miss their latest increments,
+    /// including an amount being flushed right now, but never counts anything twice. Once no thread is counting, it is exact.
+    #[inline]
+    pub fn get_exact(&self) -> T {
+        get_exact::<T>(self.shared())
+    }
+
+    /// Flushes the local counter to the global.
+    ///
+    /// Note that this only means the local counter of the thread calling is flushed. If you want to flush the local counters of multiple threads,
+    /// each thread needs to call this method, or exit.
+    ///
+    /// If every thread which incremented this counter has flushed its local counter, and no other increments have been made nor are being made,
+    /// a subsequent call to `get` is guaranteed to return the exact count.
+    /// However, if you can make use of this, consider if a flushing counter fits your usecase better.
+    // TODO: Introduce example(s).
+    #[inline]
+    pub fn flush(&self) {
+        with_local_value(self.shared(), &self.id, LocalValue::<T>::flush);
+    }
+
+    /// Flushes the local counter to the global, then resets the global counter to zero, returning its previous value.
+    ///
+    /// Just like `get`, this does not include the local counters of other threads. Those are not lost though,
+    /// but taken by a later call to `take`, once their threads have flushed or exited.
+    /// As the global counter is swapped out atomically, every flushed increment is taken exactly once.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        self.flush();
+        T::swap(&self.shared().global, T::ZERO, Ordering::Relaxed)
+    }
+
+    /// Runs `f` on the local counter of the current thread, flushing it afterwards if it reached the resolution in magnitude.
+    #[inline]
+    fn update_local(&self, f: impl FnOnce(&mut LocalValue<T>)) {
+        with_local_value(self.shared(), &self.id, |tlc| {
+            f(tlc);
+            if tlc.value.magnitude() >= self.threshold {
+                tlc.flush();
             }
-        )*
+        });
+    }
+
+    #[inline]
+    fn shared(&self) -> &Arc<Shared<T::Atomic>> {
+        self.shared
+            .get_or_init(|| Arc::new(Shared::new(new_atomic(self.start))))
+    }
+
+    // There is no set/reset method, as it would not be compatible with the guarantees made.
+    // Specifically, setting the global counter without setting all local counters too, which is hardly possible,
+    // would result in the counter going 'out of sync', resulting in an approximation to high.
+    // `take` is fine, as it only moves the count out of the global counter, without touching the local counters.
+    // TODO: Evaluate if exposing a set_local, set_global API would be useful and/or idiomatic.
+}
+
+impl<T: SignedPrimitive> ApproxCounter<T> {
+    /// Decrements the counter by one.
+    ///
+    /// Just like `inc`, this call will probably leave the value returned by `get` unchanged.
+    #[inline]
+    pub fn dec(&self) {
+        self.update_local(|tlc| tlc.sub(T::ONE));
+    }
+
+    /// Subtracts the given value from the counter.
+    ///
+    /// Just like `inc`, this call will probably leave the value returned by `get` unchanged.
+    #[inline]
+    pub fn sub(&self, val: T) {
+        self.update_local(|tlc| tlc.sub(val));
+    }
+}
+
+/// The value of the local counter of the current thread while it is being updated, and the amount flushed from it meanwhile.
+///
+/// The flushed amount is only added to the global counter after the local counter is stored,
+/// so a concurrent `get_exact` may miss it, but never counts it twice.
+struct LocalValue<T> {
+    value: T,
+    flushed: T,
+}
+
+impl<T: AtomicPrimitive> LocalValue<T> {
+    /// Adds `val` to the local counter. If that would overflow it, it is flushed first.
+    #[inline]
+    fn add(&mut self, val: T) {
+        self.value = match self.value.checked_add(val) {
+            Some(sum) => sum,
+            None => {
+                self.flush();
+                val
+            }
+        };
+    }
+
+    /// Moves the value of the local counter into the flushed amount.
+    #[inline]
+    fn flush(&mut self) {
+        self.flushed = self.flushed.wrapping_add(self.value);
+        self.value = T::ZERO;
+    }
+}
+
+impl<T: SignedPrimitive> LocalValue<T> {
+    /// Subtracts `val` from the local counter. If that would overflow it, it is flushed first.
+    #[inline]
+    fn sub(&mut self, val: T) {
+        self.value = match self.value.checked_sub(val) {
+            Some(diff) => diff,
+            None => {
+                self.flush();
+                // Only the minimum value cannot be negated, it is flushed directly.
+                val.checked_neg().unwrap_or_else(|| {
+                    self.flushed = self.flushed.wrapping_sub(val);
+                    T::ZERO
+                })
+            }
+        };
+    }
+}
+
+/// Runs `f` on the value of the local counter of the current thread belonging to the instance with the given id.
+#[inline]
+fn with_local_value<T: AtomicPrimitive, R>(
+    shared: &Arc<Shared<T::Atomic>>,
+    id: &InstanceId,
+    f: impl FnOnce(&mut LocalValue<T>) -> R,
+) -> R {
+    let flush: FlushFn<T::Atomic> = |global, local| {
+        T::fetch_add(global, T::load(local, Ordering::Relaxed), Ordering::Relaxed);
     };
+    with_local(T::fast_locals(), id.get(), shared, flush, |local| {
+        // Only this thread writes to its local counter, so there is no need for an atomic read-modify-write.
+        let mut tlc = LocalValue {
+            value: T::load(local, Ordering::Relaxed),
+            flushed: T::ZERO,
+        };
+        let res = f(&mut tlc);
+        T::store(local, tlc.value, Ordering::Relaxed);
+        if tlc.flushed != T::ZERO {
+            // Releasing makes the store above visible to any `get_exact` which acquires the new global value.
+            T::fetch_add(&shared.global, tlc.flushed, Ordering::Release);
+        }
+        res
+    })
 }
-flushing_counter![u8 AtomicU8 FlushingCounterU8, u16 AtomicU16 FlushingCounterU16, u32 AtomicU32 FlushingCounterU32, u64 AtomicU64 FlushingCounterU64, usize AtomicUsize FlushingCounterUsize, i8 AtomicI8 FlushingCounterI8, i16 AtomicI16 FlushingCounterI16, i32 AtomicI32 FlushingCounterI32, i64 AtomicI64 FlushingCounterI64, isize AtomicIsize FlushingCounterIsize];
 
-macro_rules! approx_counter {
-    ($( $primitive:ident $atomic:ident $counter:ident $resolution:ty), *) => {
+/// Sums up the global counter and all local counters.
+///
+/// The global counter is read first: Any flush it includes has already zeroed its local counter, see `with_local_value`.
+#[inline]
+fn get_exact<T: AtomicPrimitive>(shared: &Shared<T::Atomic>) -> T {
+    shared
+        .lock()
+        .iter()
+        .fold(T::load(&shared.global, Ordering::Acquire), |sum, local| {
+            sum.wrapping_add(T::load(local, Ordering::Relaxed))
+        })
+}
+
+macro_rules! aliases {
+    ($( $primitive:ident $flushing:ident $approx:ident ), *) => {
         $(
-            /// A global approximate counter.
-            ///
-            /// This counter operates by having a local counter for each thread, which is occasionally flushed to the main global counter.
-            ///
-            /// The accuracy of the counter is determined by its `resolution` and the number of threads counting on it:
-            /// The value returned by `get` is guaranteed to always be less than or to equal this number of threads multiplied with the resolution minus one
-            /// away from the actual amount of times `inc` has been called (+ start offset):
-            ///
-            /// `|get - (actual + start)| <= num_threads * (resolution - 1)`
-            ///
-            /// With resolution being >= 1. This is the only guarantee made.
-            ///
-            /// Setting the resolution to 0 or 1 will just make it a worse primitive counter, don't do that. Increasing the resolution increases this counters performance.
-            ///
-            /// This counter also features a `flush` method, which can be used to manually flush the local counter of the current thread.
-            ///
-            /// Note that this counters `inc` - (`flush`) - `get` - path does not induce a happens-before relationship, just like the flushing counters.
-            pub struct $counter {
-                // Always making the resolution unsigned was a deliberate choice.
-                // The resolution is used to upper-bound an absolute value. It cannot be negative.
-                // The thread-local counters have to be unsigned as well, to prevent unnecessary casts.
-                threshold: $resolution,
-                global_counter: $atomic,
-                // This could also be a RefCell, but this impl is also safe- or at least I hope so-
-                // and more efficient, as no runtime borrowchecking is needed.
-                thread_local_counter: &'static LocalKey<UnsafeCell<$resolution>>,
-            }
-
-            impl $counter {
-                /// Creates a new counter, with the given start value and resolution. Can be used in static contexts.
-                ///
-                /// The start value is a lower bound for the value returned by `get`, not guaranteed to be the exact value on subsequent calls.
-                #[inline]
-                pub const fn new(start: $primitive, resolution: $resolution) -> Self {
-                    thread_local!(static TL_COUNTER : UnsafeCell<$resolution> = UnsafeCell::new(0));
-                    $counter {
-                        threshold: resolution,
-                        global_counter: $atomic::new(start),
-                        thread_local_counter: &TL_COUNTER,
-                    }
-                }
-
-                /// Increments the counter by one.
-                ///
-                /// Note that this call will probably leave the value returned by `get` unchanged.
-                #[inline]
-                pub fn inc(&self) {
-                    self.thread_local_counter.with(|tlc| unsafe {
-                        // This is safe, because concurrent accesses to a thread-local are obviously not possible,
-                        // and aliasing is not possible using the counters API.
-                        let tlc = &mut *tlc.get();
-                        *tlc += 1;
-                        if *tlc >= self.threshold {
-                            // If tlc overflows as signed variant, it is still fine to reinterpret it as unsigned.
-                            // It should never actually be negative given this API.
-                            self.global_counter.fetch_add(*tlc as $primitive, Ordering::Relaxed);
-                            *tlc = 0;
-                        }
-                    });
-                }
-
-                /// Gets the current value of the counter. For more information, see the struct-level documentation.
-                ///
-                /// Especially note, that two calls to `get` with one `inc` interleaved are not guaranteed to, and almost certainely wont, return different values.
-                #[inline]
-                pub fn get(&self) -> $primitive {
-                    self.global_counter.load(Ordering::Relaxed)
-                }
-
-                /// Flushes the local counter to the global.
-                ///
-                /// Note that this only means the local counter of the thread calling is flushed. If you want to flush the local counters of multiple threads,
-                /// each thread needs to call this method.
-                ///
-                /// If every thread which incremented this counter has flushed its local counter, and no other increments have been made nor are being made,
-                /// a subsequent call to `get` is guaranteed to return the exact count.
-                /// However, if you can make use of this, consider if a flushing counter fits your usecase better.
-                // TODO: Introduce example(s).
-                #[inline]
-                pub fn flush(&self) {
-                    self.thread_local_counter.with(|tlc| unsafe {
-                        let tlc = &mut *tlc.get();
-                        self.global_counter.fetch_add(*tlc as $primitive, Ordering::Relaxed);
-                        *tlc = 0;
-                    });
-                }
-                // There is no set/reset method, as it would not be compatible with the guarantees made.
-                // Specifically, setting the global counter without setting all local counters too, which is hardly possible,
-                // would result in the counter going 'out of sync', resulting in an approximation to high.
-                // TODO: Evaluate if exposing a set_local, set_global API would be useful and/or idiomatic.
-            }
+            #[doc = concat!("A flushing counter, counting `", stringify!($primitive), "`s.")]
+            pub type $flushing = FlushingCounter<$primitive>;
+            #[doc = concat!("An approximate counter, counting `", stringify!($primitive), "`s.")]
+            pub type $approx = ApproxCounter<$primitive>;
         )*
     };
 }
-approx_counter![u8 AtomicU8 ApproxCounterU8 u8, u16 AtomicU16 ApproxCounterU16 u16, u32 AtomicU32 ApproxCounterU32 u32, u64 AtomicU64 ApproxCounterU64 u64, usize AtomicUsize ApproxCounterUsize usize, i8 AtomicI8 ApproxCounterI8 u8, i16 AtomicI16 ApproxCounterI16 u16, i32 AtomicI32 ApproxCounterI32 u32, i64 AtomicI64 ApproxCounterI64 u64, isize AtomicIsize ApproxCounterIsize usize];
+
+aliases![u8 FlushingCounterU8 ApproxCounterU8, u16 FlushingCounterU16 ApproxCounterU16, u32 FlushingCounterU32 ApproxCounterU32, u64 FlushingCounterU64 ApproxCounterU64, usize FlushingCounterUsize ApproxCounterUsize, i8 FlushingCounterI8 ApproxCounterI8, i16 FlushingCounterI16 ApproxCounterI16, i32 FlushingCounterI32 ApproxCounterI32, i64 FlushingCounterI64 ApproxCounterI64, isize FlushingCounterIsize ApproxCounterIsize, u128 FlushingCounterU128 ApproxCounterU128, i128 FlushingCounterI128 ApproxCounterI128];
 
 #[cfg(test)]
 mod tests {
@@ -246,6 +439,122 @@ mod tests {
         assert!(within_tolerance!(COUNTER.get(), 50000, GLOBAL_ACC));
     }
 
+    #[test]
+    fn approx_instances_isolated() {
+        static COUNTER: ApproxCounterU32 = ApproxCounterU32::new(0, 1024);
+        static COUNTER_2: ApproxCounterU32 = ApproxCounterU32::new(0, 1024);
+
+        for _ in 0..1000 {
+            COUNTER.inc();
+        }
+        COUNTER_2.inc();
+        COUNTER_2.flush();
+
+        assert_eq!(COUNTER_2.get(), 1);
+        assert_eq!(COUNTER.get(), 0);
+
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), 1000);
+        assert_eq!(COUNTER_2.get(), 1);
+    }
+
+    #[test]
+    fn approx_flushes_on_thread_exit() {
+        static COUNTER: ApproxCounterI64 = ApproxCounterI64::new(-5, 1024);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..1000 {
+                COUNTER.inc();
+            }
+        });
+        t_0.join().expect("Err joining thread");
+
+        assert_eq!(COUNTER.get(), 995);
+    }
+
+    #[test]
+    fn approx_get_exact() {
+        static COUNTER: ApproxCounterI16 = ApproxCounterI16::new(-100, 64);
+
+        for _ in 0..1000 {
+            COUNTER.inc();
+        }
+        assert_eq!(COUNTER.get_exact(), 900);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..1000 {
+                COUNTER.inc();
+            }
+            assert_eq!(COUNTER.get_exact(), 1900);
+        });
+        t_0.join().expect("Err joining thread");
+
+        assert_eq!(COUNTER.get_exact(), 1900);
+    }
+
+    #[test]
+    fn approx_add() {
+        static COUNTER: ApproxCounterU64 = ApproxCounterU64::new(0, 1000);
+
+        COUNTER.add(999);
+        assert_eq!(COUNTER.get(), 0);
+        COUNTER.add(2);
+        assert_eq!(COUNTER.get(), 1001);
+        COUNTER.add(4096);
+        assert_eq!(COUNTER.get(), 5097);
+    }
+
+    #[test]
+    fn approx_sub_dec_by_magnitude() {
+        static COUNTER: ApproxCounterI32 = ApproxCounterI32::new(0, 100);
+
+        COUNTER.sub(99);
+        assert_eq!(COUNTER.get(), 0);
+        COUNTER.dec();
+        assert_eq!(COUNTER.get(), -100);
+
+        for _ in 0..99 {
+            COUNTER.dec();
+        }
+        COUNTER.add(50);
+        assert_eq!(COUNTER.get(), -100);
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), -149);
+    }
+
+    #[test]
+    fn approx_local_overflow_i8() {
+        static COUNTER: ApproxCounterI8 = ApproxCounterI8::new(-100, 200);
+
+        for _ in 0..300 {
+            COUNTER.inc();
+        }
+        // The local counter cannot reach the resolution, so it is flushed before it overflows instead.
+        assert_eq!(COUNTER.get(), (-100i8).wrapping_add(127).wrapping_add(127));
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), (-100i8).wrapping_add(300u16 as i8));
+
+        COUNTER.sub(i8::MIN);
+        for _ in 0..300 {
+            COUNTER.dec();
+        }
+        COUNTER.sub(i8::MIN);
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), -100);
+    }
+
+    #[test]
+    fn approx_local_overflow_u8() {
+        static COUNTER: ApproxCounterU8 = ApproxCounterU8::new(0, u8::MAX);
+
+        for _ in 0..200 {
+            COUNTER.inc();
+        }
+        COUNTER.add(100);
+        assert_eq!(COUNTER.get(), 200);
+        assert_eq!(COUNTER.get_exact(), 44);
+    }
+
     #[test]
     fn approx_count_to_50000_single_threaded() {
         const NUM_THREADS: u32 = 1;
@@ -433,6 +742,281 @@ mod tests {
         assert!(within_tolerance!(COUNTER.get(), 50000, 0));
     }
 
+    #[test]
+    fn flushing_instances_isolated() {
+        static COUNTER: FlushingCounterU64 = FlushingCounterU64::new(0);
+        static COUNTER_2: FlushingCounterU64 = FlushingCounterU64::new(0);
+
+        for _ in 0..500 {
+            COUNTER.inc();
+        }
+        for _ in 0..300 {
+            COUNTER_2.inc();
+        }
+        COUNTER_2.flush();
+
+        assert_eq!(COUNTER_2.get(), 300);
+        assert_eq!(COUNTER.get(), 0);
+
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), 500);
+        assert_eq!(COUNTER_2.get(), 300);
+    }
+
+    #[test]
+    fn flushing_instances_isolated_par_threaded() {
+        static COUNTER: FlushingCounterU32 = FlushingCounterU32::new(0);
+        static COUNTER_2: FlushingCounterU32 = FlushingCounterU32::new(0);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+                COUNTER_2.inc();
+                COUNTER_2.inc();
+            }
+            COUNTER.flush();
+            COUNTER_2.flush();
+        });
+        let t_1 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER_2.inc();
+            }
+            COUNTER_2.flush();
+        });
+
+        t_0.join().expect("Err joining thread");
+        t_1.join().expect("Err joining thread");
+
+        assert_eq!(COUNTER.get(), 10000);
+        assert_eq!(COUNTER_2.get(), 30000);
+    }
+
+    #[test]
+    fn flushing_flushes_on_thread_exit() {
+        static COUNTER: FlushingCounterU32 = FlushingCounterU32::new(0);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+        let t_1 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+            COUNTER.flush();
+            COUNTER.inc();
+        });
+
+        t_0.join().expect("Err joining thread");
+        t_1.join().expect("Err joining thread");
+
+        assert_eq!(COUNTER.get(), 20001);
+    }
+
+    #[test]
+    fn flushing_not_static() {
+        let counter = Arc::new(FlushingCounterU32::new(3));
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        counter.inc();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        assert_eq!(counter.get(), 5003);
+
+        // The local counter of this thread outlives the counter, and flushes into its state once this thread exits.
+        let approx = ApproxCounterI8::new(0, 100);
+        approx.dec();
+        assert_eq!(approx.get_exact(), -1);
+        drop(approx);
+    }
+
+    #[test]
+    fn flushing_recycled_ids() {
+        // Every counter gets the index of the one dropped before it, but not its local counter.
+        for i in 0..100 {
+            let counter = FlushingCounterU32::new(i);
+            counter.inc();
+            assert_eq!(counter.get_exact(), i + 1);
+        }
+    }
+
+    #[test]
+    fn flushing_get_exact_par_threaded() {
+        use std::sync::{Arc, Barrier};
+
+        static COUNTER: FlushingCounterU64 = FlushingCounterU64::new(0);
+        let counted = Arc::new(Barrier::new(5));
+        let checked = Arc::new(Barrier::new(5));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let counted = counted.clone();
+                let checked = checked.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10000 {
+                        COUNTER.inc();
+                    }
+                    counted.wait();
+                    checked.wait();
+                })
+            })
+            .collect();
+
+        counted.wait();
+        assert_eq!(COUNTER.get(), 0);
+        assert_eq!(COUNTER.get_exact(), 40000);
+        checked.wait();
+
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        assert_eq!(COUNTER.get(), 40000);
+        assert_eq!(COUNTER.get_exact(), 40000);
+    }
+
+    #[test]
+    fn flushing_get_exact_while_flushing() {
+        static COUNTER: FlushingCounterU64 = FlushingCounterU64::new(0);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.add(3);
+                COUNTER.flush();
+            }
+        });
+        // A flush in progress is never counted both in the global and the local counter.
+        while !t_0.is_finished() {
+            let exact = COUNTER.get_exact();
+            assert!(exact <= 30000 && exact % 3 == 0, "{}", exact);
+        }
+        t_0.join().expect("Err joining thread");
+        assert_eq!(COUNTER.get_exact(), 30000);
+    }
+
+    #[test]
+    fn flushing_add_sub_dec() {
+        static COUNTER: FlushingCounterI64 = FlushingCounterI64::new(10);
+
+        COUNTER.add(100);
+        COUNTER.sub(30);
+        COUNTER.dec();
+        assert_eq!(COUNTER.get(), 10);
+        assert_eq!(COUNTER.get_exact(), 79);
+
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), 79);
+    }
+
+    #[test]
+    fn flushing_take() {
+        static COUNTER: FlushingCounterU32 = FlushingCounterU32::new(5);
+        COUNTER.inc();
+        assert_eq!(COUNTER.take(), 6);
+        assert_eq!(COUNTER.get_exact(), 0);
+
+        COUNTER.add(1);
+        let t_0 = std::thread::spawn(|| {
+            COUNTER.add(10);
+            COUNTER.flush();
+            COUNTER.add(20);
+            // The local counter of the main thread is not taken, but not lost either.
+            assert_eq!(COUNTER.take(), 30);
+            COUNTER.add(40);
+        });
+        t_0.join().expect("Err joining thread");
+        assert_eq!(COUNTER.take(), 41);
+        assert_eq!(COUNTER.take(), 0);
+    }
+
+    #[test]
+    fn approx_take_par_threaded() {
+        static COUNTER: ApproxCounterU64 = ApproxCounterU64::new(0, 100);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        COUNTER.inc();
+                    }
+                })
+            })
+            .collect();
+        let mut taken = 0;
+        for _ in 0..100 {
+            taken += COUNTER.take();
+        }
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        taken += COUNTER.take();
+
+        assert_eq!(taken, 50000);
+    }
+
+    #[test]
+    fn flushing_local_overflow_u8() {
+        static COUNTER: FlushingCounterU8 = FlushingCounterU8::new(0);
+
+        for _ in 0..300 {
+            COUNTER.inc();
+        }
+        assert_eq!(COUNTER.get(), u8::MAX);
+        assert_eq!(COUNTER.get_exact(), 300u16 as u8);
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), 300u16 as u8);
+    }
+
+    #[test]
+    fn flushing_local_overflow_i8() {
+        static COUNTER: FlushingCounterI8 = FlushingCounterI8::new(0);
+
+        for _ in 0..200 {
+            COUNTER.dec();
+        }
+        for _ in 0..100 {
+            COUNTER.inc();
+        }
+        COUNTER.flush();
+        assert_eq!(COUNTER.get(), -100);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..1000 {
+                COUNTER.inc();
+            }
+            for _ in 0..1000 {
+                COUNTER.dec();
+            }
+        });
+        t_0.join().expect("Err joining thread");
+        assert_eq!(COUNTER.get(), -100);
+    }
+
+    #[test]
+    fn fast_128_bit() {
+        static FLUSHING: FlushingCounterU128 = FlushingCounterU128::new(u128::MAX - 1);
+        static APPROX: ApproxCounterI128 = ApproxCounterI128::new(0, 1 << 80);
+
+        let t_0 = std::thread::spawn(|| {
+            FLUSHING.add(5);
+            for _ in 0..10 {
+                APPROX.sub(1 << 77);
+            }
+        });
+        t_0.join().expect("Err joining thread");
+
+        assert_eq!(FLUSHING.get(), 3);
+        assert_eq!(APPROX.get(), -10 * (1 << 77));
+    }
+
     #[test]
     fn flushing_count_to_50000_single_threaded() {
         static COUNTER: FlushingCounterU64 = FlushingCounterU64::new(0);
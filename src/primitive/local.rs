@@ -0,0 +1,160 @@
+use crate::sync;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use once_cell::sync::OnceCell;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::LocalKey;
+
+/// A lazily assigned, per-instance index into thread-local storage.
+///
+/// A `thread_local!` declared in a `const fn` would be shared by every instance of a counter type,
+/// so instead each instance draws its own index on first use, and the thread-locals are vectors indexed by it.
+///
+/// Indices are recycled once their instance is dropped, so the thread-local vectors only grow with the number of live instances.
+/// Thread-locals at an index may thus be left over from a dropped instance, which users tell apart by the [stamp](#method.stamp).
+#[derive(Debug)]
+pub struct InstanceId(OnceCell<Assigned>);
+
+#[derive(Debug, Clone, Copy)]
+struct Assigned {
+    index: usize,
+    stamp: u64,
+}
+
+// Indices are drawn from one sequence for all types, which keeps the thread-local vectors of one type somewhat sparse,
+// but saves each type from needing its own sequence.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+static FREE_INDICES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static NEXT_STAMP: AtomicU64 = AtomicU64::new(0);
+
+impl InstanceId {
+    #[inline]
+    pub const fn new() -> Self {
+        InstanceId(OnceCell::new())
+    }
+
+    /// Returns the index of this instance, drawing one if none was assigned yet.
+    #[inline]
+    pub fn get(&self) -> usize {
+        self.assigned().index
+    }
+
+    /// Returns a stamp unique to this instance, which, other than its index, is never reused.
+    #[inline]
+    pub fn stamp(&self) -> u64 {
+        self.assigned().stamp
+    }
+
+    #[inline]
+    fn assigned(&self) -> Assigned {
+        *self.0.get_or_init(|| Assigned {
+            index: sync::lock(&FREE_INDICES)
+                .pop()
+                .unwrap_or_else(|| NEXT_INDEX.fetch_add(1, Ordering::Relaxed)),
+            stamp: NEXT_STAMP.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+}
+
+impl Drop for InstanceId {
+    fn drop(&mut self) {
+        if let Some(assigned) = self.0.get() {
+            sync::lock(&FREE_INDICES).push(assigned.index);
+        }
+    }
+}
+
+/// Adds the value of a local counter to a global counter.
+pub type FlushFn<A> = fn(global: &A, local: &A);
+
+/// The state of one counter instance, which the local counters of all threads share ownership of.
+///
+/// Thread-locals are dropped when their thread exits, which pushes any residue into the global counter.
+/// As the local counters own the state, this works even if the counter instance itself is gone by then, so fast counters don't have to be `'static`.
+#[derive(Debug)]
+pub struct Shared<A> {
+    /// The global counter.
+    pub global: A,
+    /// The local counters of this instance, one for each thread which has used it and not exited yet.
+    pub locals: Mutex<Vec<Arc<A>>>,
+}
+
+impl<A> Shared<A> {
+    #[inline]
+    pub fn new(global: A) -> Self {
+        Shared {
+            global,
+            locals: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[inline]
+    pub fn lock(&self) -> MutexGuard<'_, Vec<Arc<A>>> {
+        sync::lock(&self.locals)
+    }
+}
+
+/// The local counter of one thread, belonging to one counter instance.
+///
+/// The local counter is an atomic only ever written to by its own thread, so other threads can read it when collecting the exact value.
+pub struct Local<A> {
+    local: Arc<A>,
+    shared: Arc<Shared<A>>,
+    flush: FlushFn<A>,
+}
+
+impl<A> Drop for Local<A> {
+    fn drop(&mut self) {
+        // Flushing and unregistering happen under the lock, so collecting threads never count the residue twice.
+        let mut locals = self.shared.lock();
+        (self.flush)(&self.shared.global, &self.local);
+        locals.retain(|local| !Arc::ptr_eq(local, &self.local));
+    }
+}
+
+/// The local counters of the current thread, for all instances of counters of one type, indexed by their instance ids.
+pub type Locals<A> = UnsafeCell<Vec<Option<Local<A>>>>;
+
+/// Runs `f` on the local counter of the current thread at index `id` in `locals`, registering it in `shared` on first use.
+/// A local counter left at the index by a dropped instance is flushed into that instances state and replaced.
+///
+/// If the thread-locals of the current thread have already been destroyed, which can only happen while the thread exits,
+/// `f` runs on a temporary local counter, which is immediately flushed to the global counter.
+#[inline]
+pub fn with_local<A: Default, R>(
+    locals: &'static LocalKey<Locals<A>>,
+    id: usize,
+    shared: &Arc<Shared<A>>,
+    flush: FlushFn<A>,
+    f: impl FnOnce(&A) -> R,
+) -> R {
+    let mut f = Some(f);
+    let res = locals.try_with(|locals| unsafe {
+        // This is safe, because concurrent accesses to a thread-local are obviously not possible,
+        // and `f` cannot reach this thread-local again using the counters API.
+        let locals = &mut *locals.get();
+        if locals.len() <= id {
+            locals.resize_with(id + 1, || None);
+        }
+        let local = match &mut locals[id] {
+            Some(local) if Arc::ptr_eq(&local.shared, shared) => local,
+            // The stale local counter keeps its state alive, so the state of this instance can never be at the same address.
+            stale => {
+                let local = Arc::new(A::default());
+                shared.lock().push(local.clone());
+                stale.insert(Local {
+                    local,
+                    shared: shared.clone(),
+                    flush,
+                })
+            }
+        };
+        (f.take().unwrap())(&local.local)
+    });
+    res.unwrap_or_else(|_| {
+        let local = A::default();
+        let res = (f.take().unwrap())(&local);
+        flush(&shared.global, &local);
+        res
+    })
+}
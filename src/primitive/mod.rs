@@ -1,7 +1,32 @@
+/// This module contains the `AtomicPrimitive` trait, which maps primitive integer types to their atomic equivalents,
+/// and the lock-based atomics backing the 128-bit counters, as well as the 64-bit counters on targets without 64-bit atomics.
+pub mod atomic;
+
 /// This module contains exact primitive counters, implemented using atomics.
 pub mod exact;
 
+/// This module contains sharded primitive counters, implemented using multiple cache-line padded atomics.
+///
+/// These counters are exact, but scale better than the exact primitive counters when a lot of threads increment concurrently.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod sharded;
+
 /// This module contains more performant hybrid counters, implemented using thread-locals and atomics.
 ///
 /// These counters rely on the assumption that thread-locals are faster than global atomics, which they are on my system. No guarantee made for yours though.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
 pub mod fast;
+
+/// This module contains windowed primitive counters, whose value only covers the recent past, implemented using rotating buckets of atomics.
+///
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod windowed;
+
+// The thread-local machinery of the fast counters.
+#[cfg(feature = "std")]
+pub(crate) mod local;
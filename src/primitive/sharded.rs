@@ -0,0 +1,256 @@
+use super::atomic::AtomicPrimitive;
+use core::mem::ManuallyDrop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Aligns its content to its own cache line, so neighbouring shards never share one.
+///
+/// 128 bytes cover the adjacent line prefetching of modern x86 processors as well.
+#[derive(Debug, Clone, Copy)]
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+// Trait methods cannot be called in const contexts, so the shards are created as primitives, and then reinterpreted as atomics,
+// just like `new_atomic` does for a single one.
+union Shards<T: Copy, A, const N: usize> {
+    vals: [CachePadded<T>; N],
+    atomics: ManuallyDrop<[CachePadded<A>; N]>,
+}
+
+/// Returns the shard index of the current thread.
+///
+/// Threads are assigned indices round-robin on first use, which spreads them more evenly over the shards than hashing their ids would.
+#[inline]
+fn shard_index() -> usize {
+    static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+    thread_local!(static INDEX : usize = NEXT_INDEX.fetch_add(1, Ordering::Relaxed));
+    // While the thread exits, its thread-locals may already be gone. Any shard will do then.
+    INDEX.try_with(|index| *index).unwrap_or(0)
+}
+
+/// A sharded primitive counter.
+///
+/// This counter consists of `N` atomics, the shards, each on its own cache line.
+/// Each thread increments only one of the shards, so threads rarely contend on the same cache line, given enough shards.
+/// `get` sums up all shards.
+///
+/// Unlike the fast counters, this counter is exact: No increment is ever held back in a thread-local,
+/// so `get` returns the exact amount of times `inc` has been called (+ start offset), once all those calls have returned.
+/// `inc` is wait-free, except for the 128-bit counters, whose atomics are backed by locks.
+///
+/// However, reading is more expensive than with an exact primitive counter, as all `N` shards have to be visited,
+/// and a counter takes up `N` cache lines of memory.
+///
+/// All operations use `Ordering::Relaxed`. Just like the fast counters, this counter is not suited to synchronize external data.
+///
+/// Usually, this counter is used through its aliases for the primitive integer types, like [ShardedCounterU32](type.ShardedCounterU32.html).
+#[derive(Debug)]
+pub struct ShardedCounter<T: AtomicPrimitive, const N: usize = 16>([CachePadded<T::Atomic>; N]);
+
+impl<T: AtomicPrimitive, const N: usize> ShardedCounter<T, N> {
+    /// Creates a new sharded counter, with the given starting value. Can be used in static contexts.
+    ///
+    /// `N` has to be at least one.
+    #[inline]
+    pub const fn new(start: T) -> Self {
+        let mut vals = [CachePadded(T::ZERO); N];
+        vals[0] = CachePadded(start);
+        // This is safe, as every atomic integer type has the same size and bit validity as its underlying integer type,
+        // and the padding places both at the start of the same, aligned cache line.
+        let shards =
+            unsafe { ManuallyDrop::into_inner(Shards::<T, T::Atomic, N> { vals }.atomics) };
+        ShardedCounter(shards)
+    }
+
+    /// Increments the counter by one.
+    ///
+    /// Other than the exact primitive counters, this does not return the previous value, as there is no single previous value.
+    #[inline]
+    pub fn inc(&self) {
+        T::fetch_add(&self.0[shard_index() % N].0, T::ONE, Ordering::Relaxed);
+    }
+
+    /// Gets the current value of the counter, summing up all shards.
+    ///
+    /// Increments which happen while the shards are summed up may or may not be included.
+    #[inline]
+    pub fn get(&self) -> T {
+        self.0.iter().fold(T::ZERO, |sum, shard| {
+            sum.wrapping_add(T::load(&shard.0, Ordering::Relaxed))
+        })
+    }
+
+    /// Sets the counter to a new value.
+    ///
+    /// This is not atomic with respect to concurrent increments, which may or may not be included afterwards.
+    #[inline]
+    pub fn set(&self, val: T) {
+        T::store(&self.0[0].0, val, Ordering::Relaxed);
+        for shard in &self.0[1..] {
+            T::store(&shard.0, T::ZERO, Ordering::Relaxed);
+        }
+    }
+
+    /// Resets the counter to zero.
+    ///
+    /// The caveats of `set` apply.
+    #[inline]
+    pub fn reset(&self) {
+        self.set(T::ZERO);
+    }
+
+    /// Resets the counter to zero, returning the previous value.
+    ///
+    /// Each shard is swapped out atomically, so every increment is counted by exactly one call to `take`, and none is lost,
+    /// even though concurrent increments may or may not be included in this call.
+    /// The start value is taken by the first call, just like any increment.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        self.0.iter().fold(T::ZERO, |sum, shard| {
+            sum.wrapping_add(T::swap(&shard.0, T::ZERO, Ordering::Relaxed))
+        })
+    }
+}
+
+macro_rules! aliases {
+    ($( $primitive:ident $counter:ident ), *) => {
+        $(
+            #[doc = concat!("A sharded primitive counter, counting `", stringify!($primitive), "`s, using `N` shards.")]
+            pub type $counter<const N: usize = 16> = ShardedCounter<$primitive, N>;
+        )*
+    };
+}
+
+aliases![u8 ShardedCounterU8, u16 ShardedCounterU16, u32 ShardedCounterU32, u64 ShardedCounterU64, usize ShardedCounterUsize, u128 ShardedCounterU128, i8 ShardedCounterI8, i16 ShardedCounterI16, i32 ShardedCounterI32, i64 ShardedCounterI64, isize ShardedCounterIsize, i128 ShardedCounterI128];
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn sharded_new_const() {
+        static COUNTER: ShardedCounterU64 = ShardedCounterU64::new(0);
+        assert_eq!(COUNTER.get(), 0);
+        COUNTER.inc();
+        assert_eq!(COUNTER.get(), 1);
+
+        static COUNTER_2: ShardedCounterI8<4> = ShardedCounterI8::new(-5);
+        assert_eq!(COUNTER_2.get(), -5);
+        COUNTER_2.inc();
+        assert_eq!(COUNTER_2.get(), -4);
+    }
+
+    #[test]
+    fn sharded_set_reset() {
+        static COUNTER: ShardedCounterU32 = ShardedCounterU32::new(7);
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..100 {
+                COUNTER.inc();
+            }
+        });
+        t_0.join().expect("Err joining thread");
+        COUNTER.inc();
+        assert_eq!(COUNTER.get(), 108);
+
+        COUNTER.set(3);
+        assert_eq!(COUNTER.get(), 3);
+        COUNTER.inc();
+        assert_eq!(COUNTER.get(), 4);
+
+        COUNTER.reset();
+        assert_eq!(COUNTER.get(), 0);
+    }
+
+    #[test]
+    fn sharded_take_par_threaded() {
+        static COUNTER: ShardedCounterI64<4> = ShardedCounterI64::new(-10);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        COUNTER.inc();
+                    }
+                })
+            })
+            .collect();
+        let mut taken = 0;
+        for _ in 0..100 {
+            taken += COUNTER.take();
+        }
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        taken += COUNTER.take();
+
+        assert_eq!(taken, 49990);
+        assert_eq!(COUNTER.get(), 0);
+    }
+
+    #[test]
+    fn sharded_128_bit() {
+        static COUNTER: ShardedCounterU128<4> = ShardedCounterU128::new(u64::MAX as u128);
+        static COUNTER_2: ShardedCounterI128 = ShardedCounterI128::new(i128::MIN + 1);
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..1000 {
+                COUNTER.inc();
+                COUNTER_2.inc();
+            }
+        });
+        t_0.join().expect("Err joining thread");
+        COUNTER.inc();
+        assert_eq!(COUNTER.get(), u64::MAX as u128 + 1001);
+        assert_eq!(COUNTER_2.take(), i128::MIN + 1001);
+        assert_eq!(COUNTER_2.get(), 0);
+    }
+
+    #[test]
+    fn sharded_single_shard() {
+        static COUNTER: ShardedCounterU16<1> = ShardedCounterU16::new(0);
+        for _ in 0..50000 {
+            COUNTER.inc();
+        }
+        assert_eq!(COUNTER.get(), 50000);
+    }
+
+    #[test]
+    fn sharded_count_to_50000_par_threaded() {
+        static COUNTER: ShardedCounterUsize = ShardedCounterUsize::new(0);
+        assert_eq!(COUNTER.get(), 0);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+        let t_1 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+        let t_2 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+        let t_3 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+        let t_4 = std::thread::spawn(|| {
+            for _ in 0..10000 {
+                COUNTER.inc();
+            }
+        });
+
+        t_0.join().expect("Err joining thread");
+        t_1.join().expect("Err joining thread");
+        t_2.join().expect("Err joining thread");
+        t_3.join().expect("Err joining thread");
+        t_4.join().expect("Err joining thread");
+
+        assert_eq!(COUNTER.get(), 50000);
+    }
+}
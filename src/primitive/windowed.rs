@@ -0,0 +1,322 @@
+use super::atomic::AtomicPrimitive;
+use super::exact::AtomicCounter;
+use once_cell::sync::OnceCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Marks a bucket, which is currently being cleared for a new epoch.
+const ROTATING: u64 = u64::MAX;
+
+/// A primitive counter, whose value only covers the recent past, e.g. the errors of the last minute.
+///
+/// The window is split into a number of buckets, each an exact primitive counter, which are reused round-robin as time passes.
+/// Each bucket is tagged with the epoch, i.e. the time slot, it counts for. A bucket is cleared when it is first counted on in a new epoch,
+/// and [get_window](#method.get_window) sums up all buckets whose epoch lies within the window.
+/// There is no background thread, so a counter which is not counted on costs nothing.
+///
+/// The window moves in steps of one bucket: The current bucket only covers part of its epoch, so `get_window` covers
+/// between `window - window / buckets` and `window` of the past. More buckets make the window more precise, but reading more expensive.
+///
+/// Time starts with the first access. Increments which race with a bucket being cleared, or which are delayed by more than the window, may be counted
+/// in a later epoch than they happened in, but are never lost as long as they are within the window.
+///
+/// All operations on the buckets use `Ordering::SeqCst`. Arithmetic operations wrap around on overflow.
+///
+/// # Example
+/// ```
+/// use global_counter::primitive::windowed::WindowedCounterU64;
+/// use std::time::Duration;
+///
+/// // Errors of the last minute, at a resolution of one second.
+/// static ERRORS: WindowedCounterU64 = WindowedCounterU64::new(Duration::from_secs(60), 60);
+///
+/// fn main(){
+///     ERRORS.inc();
+///     ERRORS.add(2);
+///     assert_eq!(ERRORS.get_window(), 3);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct WindowedCounter<T: AtomicPrimitive> {
+    window: Duration,
+    buckets: usize,
+    state: OnceCell<State<T>>,
+}
+
+#[derive(Debug)]
+struct State<T: AtomicPrimitive> {
+    created: Instant,
+    bucket_nanos: u128,
+    buckets: Box<[Bucket<T>]>,
+}
+
+#[derive(Debug)]
+struct Bucket<T: AtomicPrimitive> {
+    epoch: AtomicU64,
+    counter: AtomicCounter<T>,
+}
+
+impl<T: AtomicPrimitive> WindowedCounter<T> {
+    /// Creates a new windowed counter, covering the given window with the given number of buckets. Can be used in static contexts.
+    ///
+    /// # Panics
+    /// Panics if the number of buckets is zero, or if the window is shorter than one nanosecond per bucket.
+    #[inline]
+    pub const fn new(window: Duration, buckets: usize) -> Self {
+        assert!(buckets > 0, "A windowed counter needs at least one bucket.");
+        assert!(
+            window.as_nanos() >= buckets as u128,
+            "The window of a windowed counter has to be at least one nanosecond per bucket."
+        );
+        WindowedCounter {
+            window,
+            buckets,
+            state: OnceCell::new(),
+        }
+    }
+
+    /// Returns the window this counter covers.
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Increments the counter by one.
+    #[inline]
+    pub fn inc(&self) {
+        self.add(T::ONE);
+    }
+
+    /// Adds the given value to the counter.
+    #[inline]
+    pub fn add(&self, val: T) {
+        self.add_at(val, Instant::now());
+    }
+
+    /// Gets the sum of everything counted within the window.
+    #[inline]
+    pub fn get_window(&self) -> T {
+        self.get_window_at(Instant::now())
+    }
+
+    /// Resets the counter to zero, returning the sum of everything counted within the window.
+    ///
+    /// The buckets within the window are swapped out atomically in turn, so every increment within the window is taken by exactly one call to `take`,
+    /// and none is lost, even though concurrent increments may or may not be included in this call.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> T {
+        self.take_at(Instant::now())
+    }
+
+    /// Resets the counter to zero, clearing all buckets.
+    ///
+    /// This is not atomic with respect to concurrent increments, which may or may not be included afterwards.
+    #[inline]
+    pub fn reset(&self) {
+        if let Some(state) = self.state.get() {
+            for bucket in state.buckets.iter() {
+                bucket.counter.reset();
+            }
+        }
+    }
+
+    fn add_at(&self, val: T, now: Instant) {
+        let state = self.state();
+        let epoch = state.epoch(now);
+        let bucket = &state.buckets[(epoch % state.buckets.len() as u64) as usize];
+        loop {
+            let tag = bucket.epoch.load(Ordering::SeqCst);
+            if tag == ROTATING {
+                std::hint::spin_loop();
+                continue;
+            }
+            // A newer tag means this thread was delayed, so the increment is counted a little late.
+            if tag >= epoch {
+                break;
+            }
+            // Only one thread clears the bucket, the others wait for it, so no increment for the new epoch is cleared away.
+            if bucket
+                .epoch
+                .compare_exchange(tag, ROTATING, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                bucket.counter.reset();
+                bucket.epoch.store(epoch, Ordering::SeqCst);
+                break;
+            }
+        }
+        bucket.counter.add(val);
+    }
+
+    #[inline]
+    fn get_window_at(&self, now: Instant) -> T {
+        self.sum_window(now, AtomicCounter::get)
+    }
+
+    #[inline]
+    fn take_at(&self, now: Instant) -> T {
+        self.sum_window(now, AtomicCounter::take)
+    }
+
+    /// Sums up the values the given function returns for each bucket within the window.
+    fn sum_window(&self, now: Instant, f: impl Fn(&AtomicCounter<T>) -> T) -> T {
+        let state = self.state();
+        let epoch = state.epoch(now);
+        let len = state.buckets.len() as u64;
+        state
+            .buckets
+            .iter()
+            .filter(|bucket| {
+                let tag = bucket.epoch.load(Ordering::SeqCst);
+                // A bucket being cleared is about to count for the current epoch, so it is empty.
+                tag != ROTATING && tag.saturating_add(len) > epoch
+            })
+            .fold(T::ZERO, |sum, bucket| sum.wrapping_add(f(&bucket.counter)))
+    }
+
+    #[inline]
+    fn state(&self) -> &State<T> {
+        self.state.get_or_init(|| State {
+            created: Instant::now(),
+            bucket_nanos: self.window.as_nanos() / self.buckets as u128,
+            // Every bucket starts out empty in epoch zero, so the tags are valid from the start.
+            buckets: (0..self.buckets)
+                .map(|_| Bucket {
+                    epoch: AtomicU64::new(0),
+                    counter: AtomicCounter::new(T::ZERO),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl<T: AtomicPrimitive> State<T> {
+    #[inline]
+    fn epoch(&self, now: Instant) -> u64 {
+        (now.saturating_duration_since(self.created).as_nanos() / self.bucket_nanos) as u64
+    }
+}
+
+macro_rules! aliases {
+    ($( $primitive:ident $counter:ident ), *) => {
+        $(
+            #[doc = concat!("A windowed primitive counter, counting `", stringify!($primitive), "`s.")]
+            pub type $counter = WindowedCounter<$primitive>;
+        )*
+    };
+}
+
+aliases![u8 WindowedCounterU8, u16 WindowedCounterU16, u32 WindowedCounterU32, u64 WindowedCounterU64, usize WindowedCounterUsize, u128 WindowedCounterU128, i8 WindowedCounterI8, i16 WindowedCounterI16, i32 WindowedCounterI32, i64 WindowedCounterI64, isize WindowedCounterIsize, i128 WindowedCounterI128];
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn windowed_rotation() {
+        static COUNTER: WindowedCounterU32 = WindowedCounterU32::new(Duration::from_secs(10), 5);
+        let start = COUNTER.state().created;
+        let at = |millis| start + Duration::from_millis(millis);
+
+        COUNTER.add_at(1, at(0));
+        COUNTER.add_at(2, at(2500));
+        COUNTER.add_at(4, at(9999));
+        assert_eq!(COUNTER.get_window_at(at(9999)), 7);
+        // The bucket of the first epoch is out of the window.
+        assert_eq!(COUNTER.get_window_at(at(10000)), 6);
+        // Reused for a new epoch, so it is cleared first.
+        COUNTER.add_at(8, at(10000));
+        assert_eq!(COUNTER.get_window_at(at(11999)), 14);
+        assert_eq!(COUNTER.get_window_at(at(12000)), 12);
+        assert_eq!(COUNTER.get_window_at(at(19999)), 8);
+        assert_eq!(COUNTER.get_window_at(at(100000)), 0);
+
+        COUNTER.add_at(3, at(100000));
+        COUNTER.reset();
+        assert_eq!(COUNTER.get_window_at(at(100000)), 0);
+    }
+
+    #[test]
+    fn windowed_delayed_increment() {
+        static COUNTER: WindowedCounterI64 = WindowedCounterI64::new(Duration::from_secs(4), 4);
+        let start = COUNTER.state().created;
+        let at = |secs| start + Duration::from_secs(secs);
+
+        COUNTER.add_at(-1, at(5));
+        // Computed epoch 1 before epoch 5 reused its bucket, so it is counted in epoch 5.
+        COUNTER.add_at(-2, at(1));
+        assert_eq!(COUNTER.get_window_at(at(5)), -3);
+    }
+
+    #[test]
+    fn windowed_par_threaded() {
+        static COUNTER: WindowedCounterUsize =
+            WindowedCounterUsize::new(Duration::from_secs(3600), 60);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        COUNTER.inc();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        assert_eq!(COUNTER.get_window(), 50000);
+    }
+
+    #[test]
+    fn windowed_take() {
+        static COUNTER: WindowedCounterI16 = WindowedCounterI16::new(Duration::from_secs(10), 5);
+        let start = COUNTER.state().created;
+        let at = |millis| start + Duration::from_millis(millis);
+
+        COUNTER.add_at(1, at(0));
+        COUNTER.add_at(-2, at(2500));
+        COUNTER.add_at(4, at(9999));
+        // The bucket of the first epoch is out of the window, so it is not taken.
+        assert_eq!(COUNTER.take_at(at(10000)), 2);
+        assert_eq!(COUNTER.get_window_at(at(10000)), 0);
+        COUNTER.add_at(8, at(10000));
+        assert_eq!(COUNTER.take_at(at(10000)), 8);
+        assert_eq!(COUNTER.take_at(at(10000)), 0);
+    }
+
+    #[test]
+    fn windowed_take_par_threaded() {
+        static COUNTER: WindowedCounterU64 = WindowedCounterU64::new(Duration::from_secs(3600), 60);
+
+        let threads: Vec<_> = (0..5)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..10000 {
+                        COUNTER.inc();
+                    }
+                })
+            })
+            .collect();
+        // Taking while the threads count never loses an increment.
+        let mut taken = 0;
+        while threads.iter().any(|t| !t.is_finished()) {
+            taken += COUNTER.take();
+        }
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+        taken += COUNTER.take();
+
+        assert_eq!(taken, 50000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn windowed_no_buckets() {
+        WindowedCounterU8::new(Duration::from_secs(1), 0);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::registry::{self, Entry, Kind};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+/// The error returned by [render](fn.render.html), if counters whose names only differ in invalid characters are different kinds of metrics.
+///
+/// They would end up in the same metric family, which can only have one type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KindConflict {
+    /// The name of the metric family, with invalid characters replaced.
+    pub family: String,
+    /// The names the conflicting counters are registered under.
+    pub names: (String, String),
+}
+
+impl fmt::Display for KindConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "counters {:?} and {:?} are different kinds of metrics, but both belong to the metric family {}",
+            self.names.0, self.names.1, self.family
+        )
+    }
+}
+
+impl std::error::Error for KindConflict {}
+
+/// Renders all counters of the [registry](../registry/index.html) in the Prometheus text exposition format.
+///
+/// Each counter is rendered as one metric family, named after the name it is registered under,
+/// with invalid characters replaced by underscores. The same goes for label names, which other than metric names may not contain colons.
+/// The metric type is the [kind](../registry/enum.Kind.html) given on registration, or reported by the counter otherwise,
+/// which defaults to `gauge`. Register counters only ever going up using [register_as](../registry/fn.register_as.html) to type them `counter`.
+/// Help texts and labels given to [register_with](../registry/fn.register_with.html) are included.
+///
+/// Counters whose names only differ in invalid characters, like `a.b` and `a_b`, are merged into one family,
+/// using the first help text among them. Their samples then have to differ in their labels.
+/// Counters registered under a name without any valid character other than underscores, e.g. an empty name, are skipped,
+/// as they would not make a meaningful metric name.
+///
+/// # Errors
+///
+/// Returns a [KindConflict](struct.KindConflict.html) if counters merged into one family are different kinds of metrics.
+///
+/// Fast counters report their exact value, collecting the local counters of all live threads, so no thread has to flush before rendering.
+///
+/// # Example
+/// ```
+/// use global_counter::primitive::exact::CounterU64;
+/// use global_counter::{prometheus, registry};
+/// use global_counter::registry::Kind;
+///
+/// static REQUESTS: CounterU64 = CounterU64::new(0);
+///
+/// fn main(){
+///     registry::register_as("http_requests", Kind::Counter, "Handled HTTP requests.", &[("port", "80")], &REQUESTS);
+///     REQUESTS.inc();
+///     assert_eq!(
+///         prometheus::render().unwrap(),
+///         concat!(
+///             "# HELP http_requests Handled HTTP requests.\n",
+///             "# TYPE http_requests counter\n",
+///             "http_requests{port=\"80\"} 1\n",
+///         )
+///     );
+/// }
+/// ```
+pub fn render() -> Result<String, KindConflict> {
+    render_entries(registry::entries())
+}
+
+fn render_entries(entries: Vec<(String, Entry)>) -> Result<String, KindConflict> {
+    // Names which only differ in invalid characters end up in the same family, which may only be rendered once, with one type.
+    let mut families: BTreeMap<String, (Kind, Vec<(String, Entry)>)> = BTreeMap::new();
+    for (name, entry) in entries {
+        let family = metric_name(&name);
+        if family.chars().all(|c| c == '_') {
+            continue;
+        }
+        let kind = entry.kind.unwrap_or_else(|| entry.counter.kind());
+        let (family_kind, members) = families
+            .entry(family.clone())
+            .or_insert_with(|| (kind, Vec::new()));
+        if *family_kind != kind {
+            return Err(KindConflict {
+                family,
+                names: (members[0].0.clone(), name),
+            });
+        }
+        members.push((name, entry));
+    }
+
+    let mut out = String::new();
+    for (name, (kind, members)) in families {
+        if let Some(help) = members
+            .iter()
+            .map(|(_, entry)| &entry.help)
+            .find(|help| !help.is_empty())
+        {
+            let _ = writeln!(out, "# HELP {} {}", name, escape_help(help));
+        }
+        let kind = match kind {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        };
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (_, entry) in members {
+            let samples = entry.counter.samples();
+            for sample in samples {
+                out.push_str(&name);
+                let labels: Vec<_> = entry.labels.iter().chain(&sample.labels).collect();
+                if !labels.is_empty() {
+                    out.push('{');
+                    for (i, (label, value)) in labels.into_iter().enumerate() {
+                        if i > 0 {
+                            out.push(',');
+                        }
+                        let _ = write!(out, "{}=\"{}\"", label_name(label), escape_label(value));
+                    }
+                    out.push('}');
+                }
+                let _ = writeln!(out, " {}", sample.value);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Replaces all characters not allowed in metric names, `[a-zA-Z_:][a-zA-Z0-9_:]*`, by underscores.
+fn metric_name(name: &str) -> String {
+    sanitize(name, true)
+}
+
+/// Replaces all characters not allowed in label names, `[a-zA-Z_][a-zA-Z0-9_]*`, by underscores.
+fn label_name(name: &str) -> String {
+    sanitize(name, false)
+}
+
+fn sanitize(name: &str, colons: bool) -> String {
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'a'..='z' | 'A'..='Z' | '_' => c,
+            ':' if colons => c,
+            '0'..='9' if i > 0 => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(value: &str) -> String {
+    escape_help(value).replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::primitive::exact::{CounterI32, CounterU32, CounterUsize};
+    use crate::primitive::fast::FlushingCounterU32;
+
+    #[test]
+    fn render_families() {
+        static GAUGE: CounterI32 = CounterI32::new(0);
+        static FLUSHING: FlushingCounterU32 = FlushingCounterU32::new(0);
+        static IN_FLIGHT: CounterUsize = CounterUsize::new(2);
+        registry::register("test.render-gauge", &GAUGE);
+        registry::register("test_render_in_flight", &IN_FLIGHT);
+        registry::register_as(
+            "test_render_flushing",
+            Kind::Counter,
+            "Line one\nline \\two",
+            &[("path", "/\"quoted\""), ("0code", "200"), ("x:y", "1")],
+            &FLUSHING,
+        );
+
+        GAUGE.sub(3);
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..10 {
+                FLUSHING.inc();
+            }
+        });
+        t_0.join().expect("Err joining thread");
+        FLUSHING.inc();
+
+        let rendered = render().unwrap();
+        assert!(rendered.contains("# TYPE test_render_gauge gauge\ntest_render_gauge -3\n"));
+        // Unsigned values may go down as well, so they are not typed `counter` unless registered as one.
+        assert!(rendered.contains("# TYPE test_render_in_flight gauge\ntest_render_in_flight 2\n"));
+        assert!(rendered.contains(
+            "# HELP test_render_flushing Line one\\nline \\\\two\n\
+             # TYPE test_render_flushing counter\n\
+             test_render_flushing{path=\"/\\\"quoted\\\"\",_code=\"200\",x_y=\"1\"} 11\n"
+        ));
+    }
+
+    #[test]
+    fn render_merges_colliding_names() {
+        static DOTTED: CounterI32 = CounterI32::new(1);
+        static UNDERSCORED: CounterI32 = CounterI32::new(2);
+        registry::register_with("test.render.merged", "", &[("from", "dotted")], &DOTTED);
+        registry::register_with(
+            "test_render_merged",
+            "Merged.",
+            &[("from", "underscored")],
+            &UNDERSCORED,
+        );
+
+        let rendered = render().unwrap();
+        assert_eq!(rendered.matches("# TYPE test_render_merged ").count(), 1);
+        assert!(rendered.contains(
+            "# HELP test_render_merged Merged.\n\
+             # TYPE test_render_merged gauge\n\
+             test_render_merged{from=\"dotted\"} 1\n\
+             test_render_merged{from=\"underscored\"} 2\n"
+        ));
+    }
+
+    #[test]
+    fn render_skips_invalid_names() {
+        static EMPTY: CounterI32 = CounterI32::new(1);
+        static SYMBOLS: CounterI32 = CounterI32::new(2);
+        registry::register("", &EMPTY);
+        registry::register("-.-", &SYMBOLS);
+
+        let rendered = render().unwrap();
+        assert!(!rendered.contains("# TYPE  "));
+        assert!(!rendered.contains("# TYPE ___ "));
+        assert!(!rendered.lines().any(|line| line.starts_with(' ')));
+    }
+
+    #[test]
+    fn render_rejects_conflicting_kinds() {
+        static COUNTER: CounterU32 = CounterU32::new(1);
+        let entry = |kind| Entry {
+            counter: &COUNTER,
+            kind: Some(kind),
+            help: String::new(),
+            labels: Vec::new(),
+        };
+
+        let same_kind = vec![
+            ("test.render.kinds".to_string(), entry(Kind::Gauge)),
+            ("test_render_kinds".to_string(), entry(Kind::Gauge)),
+        ];
+        assert!(render_entries(same_kind).is_ok());
+
+        let conflicting = vec![
+            ("test.render.kinds".to_string(), entry(Kind::Counter)),
+            ("test_render_kinds".to_string(), entry(Kind::Gauge)),
+        ];
+        assert_eq!(
+            render_entries(conflicting),
+            Err(KindConflict {
+                family: "test_render_kinds".to_string(),
+                names: (
+                    "test.render.kinds".to_string(),
+                    "test_render_kinds".to_string()
+                ),
+            })
+        );
+    }
+}
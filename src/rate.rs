@@ -0,0 +1,194 @@
+use crate::registry::{Observe, Value};
+use crate::sync;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOWS: [Duration; 3] = [
+    Duration::from_secs(1),
+    Duration::from_secs(60),
+    Duration::from_secs(300),
+];
+
+/// Event rates in events per second, averaged exponentially over different time windows, just like Unix load averages.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rates {
+    /// The rate averaged over one second.
+    pub one_second: f64,
+    /// The rate averaged over one minute.
+    pub one_minute: f64,
+    /// The rate averaged over five minutes.
+    pub five_minutes: f64,
+}
+
+#[derive(Debug)]
+struct State {
+    time: Instant,
+    count: f64,
+    rates: [f64; 3],
+}
+
+/// A meter measuring the rate at which a counter is counted.
+///
+/// The meter wraps a counter, usually an exact or fast primitive counter, which is counted as usual through [counter](#method.counter).
+/// Fast counters are read exactly, so no thread has to flush for its events to show up.
+///
+/// The meter keeps exponentially weighted moving averages of the rate over one second, one minute and five minutes.
+/// There is no background thread: The averages are decayed when they are read, taking into account the time passed since the last read.
+/// Reading the meter more often makes the averages follow the actual rate more closely, but reading rarely does not skew them,
+/// as the events since the last read are assumed to be spread evenly over that time.
+///
+/// Measuring starts with the first read, which returns zero rates.
+///
+/// # Example
+/// ```
+/// use global_counter::primitive::exact::CounterU64;
+/// use global_counter::rate::RateMeter;
+///
+/// static REQUESTS: RateMeter<CounterU64> = RateMeter::new(CounterU64::new(0));
+///
+/// fn main(){
+///     REQUESTS.rates();
+///     REQUESTS.counter().inc();
+///     std::thread::sleep(std::time::Duration::from_millis(10));
+///     assert!(REQUESTS.rates().one_second > 0.0);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RateMeter<C> {
+    counter: C,
+    state: Mutex<Option<State>>,
+}
+
+impl<C: Observe> RateMeter<C> {
+    /// Creates a new meter, wrapping the given counter. Can be used in static contexts.
+    #[inline]
+    pub const fn new(counter: C) -> Self {
+        RateMeter {
+            counter,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Returns the wrapped counter.
+    #[inline]
+    pub fn counter(&self) -> &C {
+        &self.counter
+    }
+
+    /// Returns the current rates, decaying them up to now.
+    #[inline]
+    pub fn rates(&self) -> Rates {
+        self.rates_at(Instant::now())
+    }
+
+    fn rates_at(&self, now: Instant) -> Rates {
+        let mut state = sync::lock(&self.state);
+        // The counter is read under the lock, so concurrent reads never see the count going backwards.
+        let count = match self.counter.value() {
+            Value::Unsigned(val) => val as f64,
+            Value::Signed(val) => val as f64,
+        };
+        let state = state.get_or_insert_with(|| State {
+            time: now,
+            count,
+            rates: [0.0; 3],
+        });
+        let elapsed = now.saturating_duration_since(state.time).as_secs_f64();
+        if elapsed > 0.0 {
+            let rate = (count - state.count) / elapsed;
+            for (avg, window) in state.rates.iter_mut().zip(WINDOWS.iter()) {
+                // The weight of the new rate grows with the time it was measured over, relative to the window.
+                let alpha = 1.0 - (-elapsed / window.as_secs_f64()).exp();
+                *avg += alpha * (rate - *avg);
+            }
+            state.time = now;
+            state.count = count;
+        }
+        Rates {
+            one_second: state.rates[0],
+            one_minute: state.rates[1],
+            five_minutes: state.rates[2],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::primitive::exact::CounterU64;
+    use crate::primitive::fast::FlushingCounterI32;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 0.01 * expected.abs().max(1.0),
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn rate_converges_and_decays() {
+        static METER: RateMeter<CounterU64> = RateMeter::new(CounterU64::new(0));
+        let start = Instant::now();
+        assert_eq!(METER.rates_at(start), Rates::default());
+
+        // 100 events per second for 30 minutes, read once per second.
+        for second in 1..=1800 {
+            METER.counter().add(100);
+            METER.rates_at(start + Duration::from_secs(second));
+        }
+        let rates = METER.rates_at(start + Duration::from_secs(1800));
+        assert_close(rates.one_second, 100.0);
+        assert_close(rates.one_minute, 100.0);
+        assert_close(rates.five_minutes, 100.0);
+
+        // No events for one minute, read once at the end.
+        let rates = METER.rates_at(start + Duration::from_secs(1860));
+        assert_close(rates.one_second, 0.0);
+        assert_close(rates.one_minute, 100.0 * (-1.0f64).exp());
+        assert_close(rates.five_minutes, 100.0 * (-0.2f64).exp());
+    }
+
+    #[test]
+    fn rate_read_rarely() {
+        static OFTEN: RateMeter<CounterU64> = RateMeter::new(CounterU64::new(0));
+        static RARELY: RateMeter<CounterU64> = RateMeter::new(CounterU64::new(0));
+        let start = Instant::now();
+        OFTEN.rates_at(start);
+        RARELY.rates_at(start);
+
+        // With a constant rate, the averages don't depend on how often they are read.
+        for second in 1..=60 {
+            OFTEN.counter().add(10);
+            RARELY.counter().add(10);
+            OFTEN.rates_at(start + Duration::from_secs(second));
+        }
+        let often = OFTEN.rates_at(start + Duration::from_secs(60));
+        let rarely = RARELY.rates_at(start + Duration::from_secs(60));
+        assert_close(rarely.one_second, often.one_second);
+        assert_close(rarely.one_minute, often.one_minute);
+        assert_close(rarely.five_minutes, often.five_minutes);
+        assert_close(often.one_minute, 10.0 * (1.0 - (-1.0f64).exp()));
+    }
+
+    #[test]
+    fn rate_fast_counter() {
+        static METER: RateMeter<FlushingCounterI32> = RateMeter::new(FlushingCounterI32::new(0));
+        let start = Instant::now();
+        METER.rates_at(start);
+
+        let t_0 = std::thread::spawn(|| {
+            for _ in 0..50 {
+                METER.counter().inc();
+            }
+        });
+        t_0.join().expect("Err joining thread");
+        // Not flushed by this thread, but collected exactly.
+        METER.counter().sub(10);
+
+        let rates = METER.rates_at(start + Duration::from_secs(1));
+        assert_close(rates.one_second, 40.0 * (1.0 - (-1.0f64).exp()));
+    }
+}
@@ -0,0 +1,603 @@
+use crate::generic::{Backend, Counter, Inc};
+use crate::primitive::atomic::AtomicPrimitive;
+use crate::primitive::exact::{AtomicCounter, CheckedCounter, SaturatingCounter};
+use crate::primitive::fast::{ApproxCounter, FlushingCounter};
+use crate::primitive::sharded::ShardedCounter;
+use crate::primitive::windowed::WindowedCounter;
+use crate::sync;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// The value of a counter, as reported to the registry.
+///
+/// Every primitive integer type fits into one of the two variants without loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Value {
+    /// The value of a counter counting an unsigned type.
+    Unsigned(u128),
+    /// The value of a counter counting a signed type.
+    Signed(i128),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Unsigned(val) => val.fmt(f),
+            Value::Signed(val) => val.fmt(f),
+        }
+    }
+}
+
+macro_rules! value_from {
+    ($( $primitive:ident $variant:ident $wide:ident ), *) => {
+        $(
+            impl From<$primitive> for Value {
+                #[inline]
+                fn from(val: $primitive) -> Self {
+                    Value::$variant(val as $wide)
+                }
+            }
+        )*
+    };
+}
+
+// The names of the variants of `Value`, for serde.
+#[cfg(feature = "serde")]
+const VARIANTS: &[&str] = &["Unsigned", "Signed"];
+
+/// Serializes the value along with its variant, so that a signed value stays signed even if it is not negative.
+///
+/// The number is always serialized as a 128-bit integer, so this requires a format supporting those. JSON writes it as an object keyed by the variant, e.g. `{"Signed":5}`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Unsigned(val) => {
+                serializer.serialize_newtype_variant("Value", 0, VARIANTS[0], &val)
+            }
+            Value::Signed(val) => {
+                serializer.serialize_newtype_variant("Value", 1, VARIANTS[1], &val)
+            }
+        }
+    }
+}
+
+/// Deserializes a value along with its variant, as serialized.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Value {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        enum Variant {
+            Unsigned,
+            Signed,
+        }
+
+        impl<'de> serde::Deserialize<'de> for Variant {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_identifier(VariantVisitor)
+            }
+        }
+
+        struct VariantVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for VariantVisitor {
+            type Value = Variant;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("`Unsigned` or `Signed`")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, index: u64) -> Result<Variant, E> {
+                match index {
+                    0 => Ok(Variant::Unsigned),
+                    1 => Ok(Variant::Signed),
+                    _ => Err(E::invalid_value(
+                        serde::de::Unexpected::Unsigned(index),
+                        &self,
+                    )),
+                }
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<Variant, E> {
+                match name {
+                    "Unsigned" => Ok(Variant::Unsigned),
+                    "Signed" => Ok(Variant::Signed),
+                    _ => Err(E::unknown_variant(name, VARIANTS)),
+                }
+            }
+        }
+
+        struct ValueVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an unsigned or signed value")
+            }
+
+            fn visit_enum<A: serde::de::EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+                use serde::de::VariantAccess;
+                match data.variant()? {
+                    (Variant::Unsigned, val) => val.newtype_variant().map(Value::Unsigned),
+                    (Variant::Signed, val) => val.newtype_variant().map(Value::Signed),
+                }
+            }
+        }
+
+        deserializer.deserialize_enum("Value", VARIANTS, ValueVisitor)
+    }
+}
+
+value_from![u8 Unsigned u128, u16 Unsigned u128, u32 Unsigned u128, u64 Unsigned u128, u128 Unsigned u128, usize Unsigned u128, i8 Signed i128, i16 Signed i128, i32 Signed i128, i64 Signed i128, i128 Signed i128, isize Signed i128];
+
+/// One labelled value reported by a counter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sample {
+    /// The label names and values distinguishing this sample from the other samples of the same counter.
+    pub labels: Vec<(String, String)>,
+    /// The value.
+    pub value: Value,
+}
+
+/// The kind of metric a registered counter is, as reported to exporters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A value which only ever goes up, except for restarts. Exporters may compute rates from it.
+    Counter,
+    /// A value which may go up and down. This is the default, as the counters of this crate can all be set back, e.g. using `take`.
+    Gauge,
+}
+
+/// A counter which can report its current value to the registry.
+///
+/// This trait is implemented for all counters of this crate counting primitive integer types,
+/// and for lazily initialized counters, like the ones created by [global_counter!](../macro.global_counter.html).
+/// Implement it for your own types to register them, too.
+pub trait Observe: Sync {
+    /// Returns the current value.
+    fn value(&self) -> Value;
+
+    /// Returns the current values, each with its own labels.
+    ///
+    /// Counters reporting a single value don't need to implement this. The default implementation reports `value` without labels.
+    fn samples(&self) -> Vec<Sample> {
+        vec![Sample {
+            labels: Vec::new(),
+            value: self.value(),
+        }]
+    }
+
+    /// Returns the kind of metric this counter is, unless it is given on registration, like with [register_as](fn.register_as.html).
+    ///
+    /// The default implementation reports a gauge, which is correct for any counter. Only report a counter if the value never goes down.
+    fn kind(&self) -> Kind {
+        Kind::Gauge
+    }
+}
+
+impl<T: AtomicPrimitive + Into<Value>> Observe for AtomicCounter<T> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get().into()
+    }
+}
+
+impl<T: AtomicPrimitive + Into<Value>> Observe for SaturatingCounter<T> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get().into()
+    }
+}
+
+impl<T: AtomicPrimitive + Into<Value>> Observe for CheckedCounter<T> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get().into()
+    }
+}
+
+/// Reports the exact value, collecting the local counters of all live threads.
+impl<T: AtomicPrimitive + Into<Value>> Observe for FlushingCounter<T> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get_exact().into()
+    }
+}
+
+/// Reports the exact value, collecting the local counters of all live threads.
+impl<T: AtomicPrimitive + Into<Value>> Observe for ApproxCounter<T> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get_exact().into()
+    }
+}
+
+/// Reports the sum of everything counted within the window.
+impl<T: AtomicPrimitive + Into<Value>> Observe for WindowedCounter<T> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get_window().into()
+    }
+}
+
+impl<T: AtomicPrimitive + Into<Value>, const N: usize> Observe for ShardedCounter<T, N> {
+    #[inline]
+    fn value(&self) -> Value {
+        self.get().into()
+    }
+}
+
+/// Reports the value using [get_cloned](../generic/struct.Counter.html#method.get_cloned).
+///
+/// Just like `get_cloned`, this deadlocks or panics if the thread reading the registry holds a borrow of the counter.
+impl<T: Inc + Clone + Send + Into<Value>, L: Backend> Observe for Counter<T, L>
+where
+    Self: Sync,
+{
+    #[inline]
+    fn value(&self) -> Value {
+        self.get_cloned().into()
+    }
+}
+
+/// Forces the initialization of the counter, if it is not initialized yet.
+impl<C: Observe + Send, F: FnOnce() -> C + Send> Observe for Lazy<C, F> {
+    #[inline]
+    fn value(&self) -> Value {
+        Lazy::force(self).value()
+    }
+
+    #[inline]
+    fn samples(&self) -> Vec<Sample> {
+        Lazy::force(self).samples()
+    }
+
+    #[inline]
+    fn kind(&self) -> Kind {
+        Lazy::force(self).kind()
+    }
+}
+
+/// A registered counter, along with its description.
+#[derive(Clone)]
+pub(crate) struct Entry {
+    pub(crate) counter: &'static dyn Observe,
+    // If not given on registration, it is asked from the counter when needed, as lazy counters register themselves while being initialized.
+    pub(crate) kind: Option<Kind>,
+    pub(crate) help: String,
+    pub(crate) labels: Vec<(String, String)>,
+}
+
+static REGISTRY: Lazy<Mutex<BTreeMap<String, Entry>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+#[inline]
+fn lock() -> MutexGuard<'static, BTreeMap<String, Entry>> {
+    sync::lock(&REGISTRY)
+}
+
+/// Registers a counter under the given name.
+///
+/// If a counter was already registered under this name, it is replaced and returned.
+#[inline]
+pub fn register(
+    name: impl Into<String>,
+    counter: &'static dyn Observe,
+) -> Option<&'static dyn Observe> {
+    register_with(name, "", &[], counter)
+}
+
+/// Registers a counter under the given name, along with a help text and constant labels, which are added to all its samples.
+///
+/// The help text and labels are used by exporters, like the [Prometheus exporter](../prometheus/index.html).
+/// An empty help text means no help text.
+///
+/// The kind of metric is reported by the counter, see [Observe::kind](trait.Observe.html#method.kind).
+///
+/// If a counter was already registered under this name, it is replaced and returned.
+#[inline]
+pub fn register_with(
+    name: impl Into<String>,
+    help: &str,
+    labels: &[(&str, &str)],
+    counter: &'static dyn Observe,
+) -> Option<&'static dyn Observe> {
+    insert(name.into(), None, help, labels, counter)
+}
+
+/// Registers a counter under the given name as the given kind of metric, along with a help text and constant labels,
+/// just like [register_with](fn.register_with.html).
+///
+/// Use this to register e.g. an unsigned exact counter which is only ever incremented as a [Kind::Counter](enum.Kind.html#variant.Counter).
+#[inline]
+pub fn register_as(
+    name: impl Into<String>,
+    kind: Kind,
+    help: &str,
+    labels: &[(&str, &str)],
+    counter: &'static dyn Observe,
+) -> Option<&'static dyn Observe> {
+    insert(name.into(), Some(kind), help, labels, counter)
+}
+
+#[inline]
+fn insert(
+    name: String,
+    kind: Option<Kind>,
+    help: &str,
+    labels: &[(&str, &str)],
+    counter: &'static dyn Observe,
+) -> Option<&'static dyn Observe> {
+    let entry = Entry {
+        counter,
+        kind,
+        help: help.to_string(),
+        labels: labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+    };
+    lock().insert(name, entry).map(|entry| entry.counter)
+}
+
+/// Removes the counter registered under the given name, returning it.
+#[inline]
+pub fn unregister(name: &str) -> Option<&'static dyn Observe> {
+    lock().remove(name).map(|entry| entry.counter)
+}
+
+/// Returns the current value of the counter registered under the given name.
+#[inline]
+pub fn get(name: &str) -> Option<Value> {
+    // The counter is read after releasing the lock, as reading a lazy counter may register it, locking again.
+    let counter = lock().get(name).map(|entry| entry.counter);
+    counter.map(Observe::value)
+}
+
+/// Returns the names of all registered counters, in lexicographical order.
+#[inline]
+pub fn names() -> Vec<String> {
+    lock().keys().cloned().collect()
+}
+
+/// Returns the names and current values of all registered counters.
+///
+/// The values are read one after another, not at one point in time.
+///
+/// With the `serde` feature enabled, the snapshot can be serialized, e.g. into a JSON object, and deserialized again.
+pub fn snapshot() -> BTreeMap<String, Value> {
+    entries()
+        .into_iter()
+        .map(|(name, entry)| (name, entry.counter.value()))
+        .collect()
+}
+
+/// Returns all registered counters. The counters are not read under the lock, so reading them may register other counters.
+#[inline]
+pub(crate) fn entries() -> Vec<(String, Entry)> {
+    lock()
+        .iter()
+        .map(|(name, entry)| (name.clone(), entry.clone()))
+        .collect()
+}
+
+/// Creates a new global exact primitive counter, registered under the given name on first access.
+///
+/// As the counter is registered when it is first accessed, it will not be listed by the registry before that.
+/// Optionally, a help text for exporters can be given after the name.
+///
+/// # Example
+/// ```
+/// # #[macro_use] use crate::global_counter::*;
+/// use global_counter::primitive::exact::CounterU32;
+/// use global_counter::registry::{self, Value};
+///
+/// global_exact_counter!(REQUESTS, CounterU32, 0, "requests");
+///
+/// fn main(){
+///     REQUESTS.inc();
+///     assert_eq!(registry::get("requests"), Some(Value::Unsigned(1)));
+/// }
+/// ```
+#[macro_export]
+macro_rules! global_exact_counter {
+    ($name:ident, $type:ty, $value:expr, $registered_name:expr) => {
+        $crate::global_exact_counter!($name, $type, $value, $registered_name, "");
+    };
+    ($name:ident, $type:ty, $value:expr, $registered_name:expr, $help:expr) => {
+        static $name: $crate::global_counter_macro_dependencies::Lazy<$type> =
+            $crate::global_counter_macro_dependencies::Lazy::new(|| {
+                $crate::registry::register_with($registered_name, $help, &[], &$name);
+                <$type>::new($value)
+            });
+    };
+}
+
+/// Creates a new global flushing counter, registered under the given name on first access.
+///
+/// The registry reports the exact value of the counter.
+/// As the counter is registered when it is first accessed, it will not be listed by the registry before that.
+/// Optionally, a help text for exporters can be given after the name.
+///
+/// # Example
+/// ```
+/// # #[macro_use] use crate::global_counter::*;
+/// use global_counter::primitive::fast::FlushingCounterU64;
+/// use global_counter::registry::{self, Value};
+///
+/// global_flushing_counter!(BYTES_SENT, FlushingCounterU64, 0, "bytes_sent");
+///
+/// fn main(){
+///     BYTES_SENT.add(512);
+///     assert_eq!(registry::get("bytes_sent"), Some(Value::Unsigned(512)));
+/// }
+/// ```
+#[macro_export]
+macro_rules! global_flushing_counter {
+    ($name:ident, $type:ty, $value:expr, $registered_name:expr) => {
+        $crate::global_flushing_counter!($name, $type, $value, $registered_name, "");
+    };
+    ($name:ident, $type:ty, $value:expr, $registered_name:expr, $help:expr) => {
+        static $name: $crate::global_counter_macro_dependencies::Lazy<$type> =
+            $crate::global_counter_macro_dependencies::Lazy::new(|| {
+                $crate::registry::register_with($registered_name, $help, &[], &$name);
+                <$type>::new($value)
+            });
+    };
+}
+
+/// Creates a new global approximate counter with the given resolution, registered under the given name on first access.
+///
+/// The registry reports the exact value of the counter.
+/// As the counter is registered when it is first accessed, it will not be listed by the registry before that.
+/// Optionally, a help text for exporters can be given after the name.
+///
+/// # Example
+/// ```
+/// # #[macro_use] use crate::global_counter::*;
+/// use global_counter::primitive::fast::ApproxCounterI32;
+/// use global_counter::registry::{self, Value};
+///
+/// global_approx_counter!(BALANCE, ApproxCounterI32, 0, 1024, "balance");
+///
+/// fn main(){
+///     BALANCE.sub(7);
+///     assert_eq!(registry::get("balance"), Some(Value::Signed(-7)));
+/// }
+/// ```
+#[macro_export]
+macro_rules! global_approx_counter {
+    ($name:ident, $type:ty, $value:expr, $resolution:expr, $registered_name:expr) => {
+        $crate::global_approx_counter!($name, $type, $value, $resolution, $registered_name, "");
+    };
+    ($name:ident, $type:ty, $value:expr, $resolution:expr, $registered_name:expr, $help:expr) => {
+        static $name: $crate::global_counter_macro_dependencies::Lazy<$type> =
+            $crate::global_counter_macro_dependencies::Lazy::new(|| {
+                $crate::registry::register_with($registered_name, $help, &[], &$name);
+                <$type>::new($value, $resolution)
+            });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::primitive::exact::CounterI64;
+    use crate::primitive::fast::{ApproxCounterU16, FlushingCounterUsize};
+    use crate::primitive::sharded::ShardedCounterU8;
+
+    #[test]
+    fn register_get_unregister() {
+        static COUNTER: CounterI64 = CounterI64::new(-3);
+        assert!(register("test_register_get_unregister", &COUNTER).is_none());
+        assert_eq!(get("test_register_get_unregister"), Some(Value::Signed(-3)));
+        COUNTER.inc();
+        assert_eq!(get("test_register_get_unregister"), Some(Value::Signed(-2)));
+
+        static COUNTER_2: ShardedCounterU8 = ShardedCounterU8::new(4);
+        assert!(register("test_register_get_unregister", &COUNTER_2).is_some());
+        assert_eq!(
+            get("test_register_get_unregister"),
+            Some(Value::Unsigned(4))
+        );
+
+        assert!(unregister("test_register_get_unregister").is_some());
+        assert_eq!(get("test_register_get_unregister"), None);
+    }
+
+    #[test]
+    fn registering_macros() {
+        global_exact_counter!(
+            EXACT,
+            crate::primitive::exact::CounterU32,
+            5,
+            "test_registering_macros_exact"
+        );
+        global_flushing_counter!(
+            FLUSHING,
+            FlushingCounterUsize,
+            0,
+            "test_registering_macros_flushing"
+        );
+        global_approx_counter!(
+            APPROX,
+            ApproxCounterU16,
+            0,
+            100,
+            "test_registering_macros_approx"
+        );
+
+        // Not accessed yet, so not registered yet.
+        assert_eq!(get("test_registering_macros_exact"), None);
+
+        EXACT.inc();
+        let t_0 = std::thread::spawn(|| {
+            FLUSHING.add(3);
+            APPROX.inc();
+        });
+        t_0.join().expect("Err joining thread");
+        FLUSHING.inc();
+
+        let snapshot: Vec<_> = snapshot()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("test_registering_macros"))
+            .collect();
+        assert_eq!(
+            snapshot,
+            vec![
+                (
+                    "test_registering_macros_approx".to_string(),
+                    Value::Unsigned(1)
+                ),
+                (
+                    "test_registering_macros_exact".to_string(),
+                    Value::Unsigned(6)
+                ),
+                (
+                    "test_registering_macros_flushing".to_string(),
+                    Value::Unsigned(4)
+                ),
+            ]
+        );
+        assert!(names().contains(&"test_registering_macros_exact".to_string()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_serde() {
+        use crate::primitive::sharded::ShardedCounterU64;
+        static COUNTER: CounterI64 = CounterI64::new(-7);
+        static COUNTER_2: ShardedCounterU64 = ShardedCounterU64::new(u64::MAX);
+        register("test_snapshot_serde_signed", &COUNTER);
+        register("test_snapshot_serde_unsigned", &COUNTER_2);
+
+        let snapshot: BTreeMap<_, _> = snapshot()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("test_snapshot_serde"))
+            .collect();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            json,
+            r#"{"test_snapshot_serde_signed":{"Signed":-7},"test_snapshot_serde_unsigned":{"Unsigned":18446744073709551615}}"#
+        );
+
+        let restored: BTreeMap<String, Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+
+        // The variant survives the round trip, even if the number alone does not tell it.
+        for value in [
+            Value::Signed(5),
+            Value::Signed(0),
+            Value::Unsigned(0),
+            Value::Signed(i128::MIN),
+            Value::Unsigned(u128::MAX),
+        ] {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+        }
+        assert!(serde_json::from_str::<Value>(r#"{"Signed":5,"Unsigned":5}"#).is_err());
+        assert!(serde_json::from_str::<Value>("5").is_err());
+    }
+}
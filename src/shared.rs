@@ -0,0 +1,561 @@
+use memmap2::MmapMut;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Layout of a region: A header, followed by the slots.
+// Header: magic number, number of slots, padding.
+// Slot: state word, value, name (zero padded).
+// State word: the state in the lowest byte, the generation of the claim in the next three bytes,
+// and the id of the claiming process in the upper four bytes.
+const MAGIC: u64 = u64::from_le_bytes(*b"glblctr1");
+const HEADER_LEN: usize = 64;
+const SLOT_LEN: usize = 64;
+const NAME_OFFSET: usize = 16;
+
+/// The maximum length of a counter name in bytes.
+pub const MAX_NAME_LEN: usize = SLOT_LEN - NAME_OFFSET;
+
+// States of a slot.
+const EMPTY: u64 = 0;
+const CLAIMING: u64 = 1;
+const READY: u64 = 2;
+const STATE_MASK: u64 = 0xff;
+const GENERATION_MASK: u64 = 0xff_ffff;
+
+// Claiming a slot only takes a few writes, so a slot being claimed for longer was left behind by a process dying or hanging meanwhile.
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The mapped file, shared by a region and all its counters.
+struct Mapping {
+    // Keeps the memory mapped. All accesses go through `base`.
+    _map: MmapMut,
+    base: *mut u8,
+    slots: usize,
+}
+
+// This is safe, as the mapped memory is only ever accessed through atomics,
+// except for slot names, which are only written before and read after publishing them through the atomic slot state.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    #[inline]
+    fn atomic(&self, offset: usize) -> &AtomicU64 {
+        debug_assert!(offset % 8 == 0 && offset + 8 <= HEADER_LEN + self.slots * SLOT_LEN);
+        // This is safe, as the mapping is page aligned, the offset is aligned and in bounds,
+        // and AtomicU64 has the same in-memory representation as u64.
+        unsafe { &*(self.base.add(offset) as *const AtomicU64) }
+    }
+
+    #[inline]
+    fn slot(&self, index: usize) -> usize {
+        HEADER_LEN + index * SLOT_LEN
+    }
+
+    #[inline]
+    fn name(&self, slot: usize) -> &[u8] {
+        // This is safe, as names are written only once, before publishing them, and are only read after.
+        let name =
+            unsafe { std::slice::from_raw_parts(self.base.add(slot + NAME_OFFSET), MAX_NAME_LEN) };
+        let len = name.iter().position(|&b| b == 0).unwrap_or(MAX_NAME_LEN);
+        &name[..len]
+    }
+}
+
+/// A named region of memory shared between processes, holding many counters.
+///
+/// The region is backed by a memory-mapped file. All processes opening the same file share the same counters.
+/// On Linux, a file in `/dev/shm` makes the region a shared-memory segment, which never touches the disk.
+/// Otherwise, the counters also persist in the file, just like with any other memory-mapped file.
+///
+/// Counters are identified by a name of at most [MAX_NAME_LEN](constant.MAX_NAME_LEN.html) bytes, and are created on first access.
+/// Each counter takes up one slot of the region. The number of slots is fixed when the region is created.
+///
+/// If a process dies while creating a counter, the slot it was claiming is taken over by the next process creating a counter.
+/// On unix, dead processes are detected right away. Otherwise, and if the claiming process is still alive, but has not finished its claim,
+/// the slot is taken over once it has been claimed for a second.
+///
+/// # Example
+/// ```no_run
+/// use global_counter::shared::SharedRegion;
+///
+/// fn main(){
+///     // Every worker process opens the region and gets the same counter.
+///     let region = SharedRegion::open("/dev/shm/my_service_counters", 256).unwrap();
+///     let requests = region.counter("requests").unwrap();
+///     requests.inc();
+///     println!("{} requests served by all workers", requests.get());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SharedRegion(Arc<Mapping>);
+
+impl SharedRegion {
+    /// Opens the region backed by the file at the given path, creating it with the given number of slots if it does not exist yet.
+    ///
+    /// If the file exists, its number of slots is used, regardless of the given one.
+    /// Fails if the file exists but does not hold a region.
+    pub fn open(path: impl AsRef<Path>, slots: usize) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                create(path, slots)?;
+                OpenOptions::new().read(true).write(true).open(path)?
+            }
+            Err(e) => return Err(e),
+        };
+        // This is safe, as long as no one modifies the file in other ways than through a region.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        let len = map.len();
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a shared counter region");
+        if len < HEADER_LEN || (len - HEADER_LEN) % SLOT_LEN != 0 {
+            return Err(invalid());
+        }
+        let mapping = Mapping {
+            base: map.as_mut_ptr(),
+            _map: map,
+            slots: (len - HEADER_LEN) / SLOT_LEN,
+        };
+        if mapping.atomic(0).load(Ordering::Acquire) != MAGIC
+            || mapping.atomic(8).load(Ordering::Relaxed) != mapping.slots as u64
+        {
+            return Err(invalid());
+        }
+        Ok(SharedRegion(Arc::new(mapping)))
+    }
+
+    /// Returns the number of slots of this region, which is the maximum number of counters it can hold.
+    #[inline]
+    pub fn slots(&self) -> usize {
+        self.0.slots
+    }
+
+    /// Returns the counter with the given name, creating it, starting from zero, if it does not exist yet.
+    ///
+    /// Fails if the name is empty, longer than [MAX_NAME_LEN](constant.MAX_NAME_LEN.html) bytes or contains a null byte,
+    /// or if the counter does not exist and all slots are taken.
+    ///
+    /// Existing counters are returned right away. Creating a counter may wait for other processes creating counters,
+    /// up to a second if one of them died or hangs meanwhile.
+    pub fn counter(&self, name: &str) -> io::Result<SharedCounterU64> {
+        let name = name.as_bytes();
+        if name.is_empty() || name.len() > MAX_NAME_LEN || name.contains(&0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid shared counter name",
+            ));
+        }
+        let mapping = &self.0;
+        // Existing counters are found without waiting for slots being claimed.
+        for index in 0..mapping.slots {
+            let slot = mapping.slot(index);
+            match mapping.atomic(slot).load(Ordering::Acquire) & STATE_MASK {
+                EMPTY => break,
+                READY if mapping.name(slot) == name => return Ok(self.handle(slot)),
+                _ => {}
+            }
+        }
+        // Slots are claimed in order, and every process waits for slots being claimed to be published before looking further.
+        // So if two processes create the same counter at the same time, they end up at the same slot.
+        for index in 0..mapping.slots {
+            let slot = mapping.slot(index);
+            let state = mapping.atomic(slot);
+            let mut seen = None;
+            loop {
+                let word = state.load(Ordering::Acquire);
+                match word & STATE_MASK {
+                    READY => {
+                        if mapping.name(slot) == name {
+                            return Ok(self.handle(slot));
+                        }
+                        break;
+                    }
+                    CLAIMING if !abandoned(word, &mut seen) => std::thread::yield_now(),
+                    // Empty, or claimed by a process which died or hangs.
+                    _ => {
+                        let claim = claim(word);
+                        if state
+                            .compare_exchange(word, claim, Ordering::Acquire, Ordering::Relaxed)
+                            .is_ok()
+                            && self.publish(slot, name, claim)
+                        {
+                            return Ok(self.handle(slot));
+                        }
+                    }
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "shared counter region is full",
+        ))
+    }
+
+    /// Returns the names and current values of all counters in this region, in the order they were created.
+    pub fn counters(&self) -> Vec<(String, u64)> {
+        let mapping = &self.0;
+        (0..mapping.slots)
+            .map(|index| mapping.slot(index))
+            .map(|slot| {
+                (
+                    slot,
+                    mapping.atomic(slot).load(Ordering::Acquire) & STATE_MASK,
+                )
+            })
+            .take_while(|&(_, state)| state != EMPTY)
+            .filter(|&(_, state)| state == READY)
+            .map(|(slot, _)| {
+                (
+                    String::from_utf8_lossy(mapping.name(slot)).into_owned(),
+                    mapping.atomic(slot + 8).load(Ordering::SeqCst),
+                )
+            })
+            .collect()
+    }
+
+    /// Writes the name into the claimed slot and publishes it, returning whether the claim was still held by this process.
+    ///
+    /// A process hanging for longer than the timeout loses its claim to another process, and then has to look for a slot again.
+    fn publish(&self, slot: usize, name: &[u8], claim: u64) -> bool {
+        let mapping = &self.0;
+        let state = mapping.atomic(slot);
+        if state.load(Ordering::Relaxed) != claim {
+            return false;
+        }
+        // This is safe, as only the process holding the claim writes to the slot, and no one reads the name before it is published.
+        unsafe {
+            let dst = mapping.base.add(slot + NAME_OFFSET);
+            std::ptr::write_bytes(dst, 0, MAX_NAME_LEN);
+            std::ptr::copy_nonoverlapping(name.as_ptr(), dst, name.len());
+        }
+        mapping.atomic(slot + 8).store(0, Ordering::Relaxed);
+        state
+            .compare_exchange(
+                claim,
+                READY | (claim & !STATE_MASK),
+                Ordering::Release,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    #[inline]
+    fn handle(&self, slot: usize) -> SharedCounterU64 {
+        SharedCounterU64 {
+            mapping: self.0.clone(),
+            value: slot + 8,
+        }
+    }
+}
+
+impl fmt::Debug for SharedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedRegion")
+            .field("slots", &self.0.slots)
+            .finish()
+    }
+}
+
+/// Returns the state word claiming a slot for this process, replacing the given state word.
+///
+/// Each claim of a slot gets the next generation, so a claim taken over by another process never looks like the one it replaced,
+/// even if both processes have the same id.
+#[inline]
+fn claim(word: u64) -> u64 {
+    let generation = ((word >> 8) + 1) & GENERATION_MASK;
+    CLAIMING | generation << 8 | (std::process::id() as u64) << 32
+}
+
+/// Returns whether the claim in the given state word was abandoned, because the claiming process died,
+/// or because it has not been published for longer than the timeout.
+///
+/// `seen` tracks since when the claim is seen, so the timeout restarts whenever the slot is claimed anew.
+fn abandoned(word: u64, seen: &mut Option<(u64, Instant)>) -> bool {
+    let since = match *seen {
+        Some((seen_word, since)) if seen_word == word => since,
+        _ => seen.insert((word, Instant::now())).1,
+    };
+    since.elapsed() > CLAIM_TIMEOUT || !alive((word >> 32) as u32)
+}
+
+/// Returns whether the process with the given id may still be alive.
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    // Ids which are no valid process ids would address process groups, so they are left to the timeout.
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return true,
+    };
+    // This is safe, as signal 0 is never delivered, but only checks whether the process exists.
+    let res = unsafe { libc::kill(pid, 0) };
+    res == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Returns whether the process with the given id may still be alive.
+///
+/// Without a way to check, every process may be, so abandoned claims are only detected by the timeout.
+#[cfg(not(unix))]
+fn alive(_pid: u32) -> bool {
+    true
+}
+
+/// Creates a region file, unless another process or thread is faster.
+fn create(path: &Path, slots: usize) -> io::Result<()> {
+    static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+    // The region is set up in a temporary file, which is then linked to the actual path,
+    // so no other process ever opens a half-initialized region.
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let res = init(Path::new(&tmp), slots).and_then(|()| fs::hard_link(&tmp, path));
+    let _ = fs::remove_file(&tmp);
+    match res {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        res => res,
+    }
+}
+
+fn init(path: &Path, slots: usize) -> io::Result<()> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.set_len((HEADER_LEN + slots * SLOT_LEN) as u64)?;
+    // This is safe, as no one else knows about this file yet.
+    let mut map = unsafe { MmapMut::map_mut(&file)? };
+    map[8..16].copy_from_slice(&(slots as u64).to_ne_bytes());
+    map[..8].copy_from_slice(&MAGIC.to_ne_bytes());
+    map.flush()
+}
+
+/// A counter living in a [SharedRegion](struct.SharedRegion.html), shared between processes.
+///
+/// This counter behaves just like an exact primitive counter. All operations use `Ordering::SeqCst`.
+///
+/// Handles are cheap to clone, and keep the region mapped.
+#[derive(Clone)]
+pub struct SharedCounterU64 {
+    mapping: Arc<Mapping>,
+    value: usize,
+}
+
+impl SharedCounterU64 {
+    /// Increments the counter by one, returning the previous value.
+    #[inline]
+    pub fn inc(&self) -> u64 {
+        self.atomic().fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Adds the given value to the counter, returning the previous value.
+    #[inline]
+    pub fn add(&self, val: u64) -> u64 {
+        self.atomic().fetch_add(val, Ordering::SeqCst)
+    }
+
+    /// Gets the current value of the counter.
+    #[inline]
+    pub fn get(&self) -> u64 {
+        self.atomic().load(Ordering::SeqCst)
+    }
+
+    /// Sets the counter to a new value.
+    #[inline]
+    pub fn set(&self, val: u64) {
+        self.atomic().store(val, Ordering::SeqCst);
+    }
+
+    /// Resets the counter to zero.
+    #[inline]
+    pub fn reset(&self) {
+        self.set(0);
+    }
+
+    /// Resets the counter to zero, returning the previous value, in a single atomic swap.
+    #[doc(alias = "get_and_reset")]
+    #[inline]
+    pub fn take(&self) -> u64 {
+        self.atomic().swap(0, Ordering::SeqCst)
+    }
+
+    #[inline]
+    fn atomic(&self) -> &AtomicU64 {
+        self.mapping.atomic(self.value)
+    }
+}
+
+impl fmt::Debug for SharedCounterU64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedCounterU64")
+            .field(&self.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "global_counter_shared_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn shared_between_mappings() {
+        let path = temp_path("between_mappings");
+        let region = SharedRegion::open(&path, 4).unwrap();
+        // A second mapping of the same file, just like another process would have.
+        let other = SharedRegion::open(&path, 100).unwrap();
+        assert_eq!(other.slots(), 4);
+
+        let counter = region.counter("requests").unwrap();
+        counter.inc();
+        counter.add(4);
+        assert_eq!(other.counter("requests").unwrap().get(), 5);
+        other.counter("errors").unwrap().set(3);
+
+        assert_eq!(
+            region.counters(),
+            vec![("requests".to_string(), 5), ("errors".to_string(), 3)]
+        );
+        counter.reset();
+        assert_eq!(other.counter("requests").unwrap().get(), 0);
+        counter.add(2);
+        assert_eq!(other.counter("requests").unwrap().take(), 2);
+        assert_eq!(counter.get(), 0);
+
+        drop(region);
+        drop(other);
+        let reopened = SharedRegion::open(&path, 4).unwrap();
+        assert_eq!(reopened.counter("errors").unwrap().get(), 3);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_invalid() {
+        let path = temp_path("invalid");
+        let region = SharedRegion::open(&path, 1).unwrap();
+        assert!(region.counter("").is_err());
+        assert!(region.counter(&"x".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(region.counter(&"x".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(region.counter("other").is_err());
+        fs::remove_file(&path).unwrap();
+
+        fs::write(&path, b"not a region").unwrap();
+        assert!(SharedRegion::open(&path, 1).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_abandoned_claim() {
+        let path = temp_path("abandoned_claim");
+        let region = SharedRegion::open(&path, 3).unwrap();
+        let counter = region.counter("before").unwrap();
+        region.counter("hanging").unwrap();
+        region.counter("later").unwrap().set(4);
+        let claimed = region.0.atomic(region.0.slot(1));
+        // Just like a live process hanging while claiming the second slot, while another process found the third one.
+        claimed.store(claim(claimed.load(Ordering::Relaxed)), Ordering::Release);
+
+        // Existing counters do not wait for the claim.
+        let start = Instant::now();
+        assert_eq!(region.counter("later").unwrap().get(), 4);
+        counter.inc();
+        assert_eq!(region.counter("before").unwrap().get(), 1);
+        assert!(start.elapsed() < CLAIM_TIMEOUT);
+        assert_eq!(
+            region.counters(),
+            vec![("before".to_string(), 1), ("later".to_string(), 4)]
+        );
+
+        // New counters take over the slot, once the claim timed out.
+        let after = region.counter("after").unwrap();
+        assert!(start.elapsed() > CLAIM_TIMEOUT);
+        after.inc();
+        assert_eq!(
+            region.counters(),
+            vec![
+                ("before".to_string(), 1),
+                ("after".to_string(), 1),
+                ("later".to_string(), 4)
+            ]
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shared_claim_of_dead_process() {
+        let path = temp_path("claim_of_dead_process");
+        let region = SharedRegion::open(&path, 1).unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+        // Just like the exited process died while claiming the slot.
+        let claimed = region.0.atomic(region.0.slot(0));
+        claimed.store(CLAIMING | 1 << 8 | (dead as u64) << 32, Ordering::Release);
+
+        let start = Instant::now();
+        region.counter("after").unwrap().inc();
+        assert!(start.elapsed() < CLAIM_TIMEOUT);
+        assert_eq!(region.counters(), vec![("after".to_string(), 1)]);
+        assert_eq!(
+            (claimed.load(Ordering::Relaxed) >> 8) & GENERATION_MASK,
+            2,
+            "The takeover is the second claim of the slot"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_count_to_50000_par_threaded() {
+        let path = temp_path("par_threaded");
+
+        // Every thread opens and creates on its own, racing for the same file and slot.
+        let threads: Vec<_> = (0..5)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let region = SharedRegion::open(&path, 8).unwrap();
+                    region.counter(&format!("thread_{}", i)).unwrap().inc();
+                    let counter = region.counter("all").unwrap();
+                    for _ in 0..10000 {
+                        counter.inc();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().expect("Err joining thread");
+        }
+
+        let region = SharedRegion::open(&path, 8).unwrap();
+        assert_eq!(region.counter("all").unwrap().get(), 50000);
+        assert_eq!(region.counters().len(), 6);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
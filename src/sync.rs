@@ -0,0 +1,23 @@
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// The locks in this crate only guard state which is never left inconsistent by a panic:
+// Every update of it is a single, non-panicking operation, like pushing to a list or inserting into a map.
+// So poisoning can safely be ignored, and a panic on one thread never takes the counters of all other threads down with it.
+
+/// Locks the mutex, ignoring poisoning.
+#[inline]
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks the rwlock for reading, ignoring poisoning.
+#[inline]
+pub(crate) fn read<T: ?Sized>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks the rwlock for writing, ignoring poisoning.
+#[inline]
+pub(crate) fn write<T: ?Sized>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}